@@ -0,0 +1,337 @@
+#![no_std]
+
+extern crate alloc;
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use core::cmp::Reverse;
+use core::time::Duration;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Vruntime(u128);
+
+impl Vruntime {
+    pub fn new(x: u128) -> Self {
+        Self(x)
+    }
+    pub fn value(self) -> u128 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Priority(u32);
+
+impl Priority {
+    pub const MIN: Self = Self(1);
+    pub const MAX: Self = Self(1000000);
+    pub const DEFAULT: Self = Self(1000);
+    pub fn new(x: u32) -> Option<Self> {
+        if (Self::MIN.0..=Self::MAX.0).contains(&x) {
+            Some(Self(x))
+        } else {
+            None
+        }
+    }
+    pub fn value(self) -> u32 {
+        self.0
+    }
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
+pub enum ScheduleClass {
+    Fifo = 0,
+    Normal = 1,
+    Idle = 2,
+}
+
+pub trait Schedulable {
+    fn class(&self) -> ScheduleClass;
+    fn priority(&self) -> Priority;
+    fn vruntime(&self) -> Vruntime;
+    fn set_vruntime(&self, vruntime: Vruntime);
+    fn allowed(&self, hart: usize) -> bool;
+    fn swap_queued(&self, queued: bool) -> bool;
+    fn charge(&self, elapsed: Duration) {
+        if self.class() == ScheduleClass::Normal {
+            let step = elapsed.as_nanos() * Priority::DEFAULT.value() as u128
+                / self.priority().value() as u128;
+            self.set_vruntime(Vruntime::new(self.vruntime().value() + step));
+        }
+    }
+}
+
+pub struct SchedulerQueue<T> {
+    fifo: BTreeMap<(Reverse<Priority>, u64), Arc<T>>,
+    ready: BTreeMap<(Vruntime, usize), Arc<T>>,
+    idle: VecDeque<Arc<T>>,
+    sequence: u64,
+    min_vruntime: Vruntime,
+}
+
+impl<T: Schedulable> SchedulerQueue<T> {
+    pub fn new() -> Self {
+        Self {
+            fifo: BTreeMap::new(),
+            ready: BTreeMap::new(),
+            idle: VecDeque::new(),
+            sequence: 0,
+            min_vruntime: Vruntime::new(0),
+        }
+    }
+    pub fn count(&self) -> usize {
+        self.fifo.len() + self.ready.len() + self.idle.len()
+    }
+    pub fn min_vruntime(&self) -> Vruntime {
+        self.min_vruntime
+    }
+    pub fn place(&self, task: &T, credit: Duration) {
+        if task.class() == ScheduleClass::Normal {
+            let floor = self.min_vruntime.value().saturating_sub(credit.as_nanos());
+            let vruntime = u128::max(task.vruntime().value(), floor);
+            task.set_vruntime(Vruntime::new(vruntime));
+        }
+    }
+    pub fn insert(&mut self, task: Arc<T>) {
+        if task.swap_queued(true) {
+            return;
+        }
+        match task.class() {
+            ScheduleClass::Fifo => {
+                self.sequence += 1;
+                self.fifo
+                    .insert((Reverse(task.priority()), self.sequence), task);
+            }
+            ScheduleClass::Normal => {
+                self.ready
+                    .insert((task.vruntime(), Arc::as_ptr(&task) as usize), task);
+            }
+            ScheduleClass::Idle => self.idle.push_back(task),
+        }
+    }
+    pub fn pop(&mut self) -> Option<Arc<T>> {
+        self.next().inspect(|task| {
+            task.swap_queued(false);
+        })
+    }
+    pub fn steal(&mut self, hart: usize) -> Option<Arc<T>> {
+        self.next_allowed(hart).inspect(|task| {
+            task.swap_queued(false);
+        })
+    }
+    pub fn stealable(&self, hart: usize) -> bool {
+        let mut tasks = self
+            .fifo
+            .values()
+            .chain(self.ready.values())
+            .chain(self.idle.iter());
+        tasks.any(|task| task.allowed(hart))
+    }
+    fn next(&mut self) -> Option<Arc<T>> {
+        if let Some((_, value)) = self.fifo.pop_first() {
+            return Some(value);
+        }
+        if let Some(((vruntime, _), value)) = self.ready.pop_first() {
+            self.min_vruntime = Vruntime::max(self.min_vruntime, vruntime);
+            return Some(value);
+        }
+        self.idle.pop_front()
+    }
+    fn next_allowed(&mut self, hart: usize) -> Option<Arc<T>> {
+        let ready = self.ready.iter().rev().find(|(_, task)| task.allowed(hart));
+        if let Some((&key, _)) = ready {
+            return self.ready.remove(&key);
+        }
+        if let Some(i) = self.idle.iter().rposition(|task| task.allowed(hart)) {
+            return self.idle.remove(i);
+        }
+        let fifo = self.fifo.iter().rev().find(|(_, task)| task.allowed(hart));
+        let (&key, _) = fifo?;
+        self.fifo.remove(&key)
+    }
+}
+
+impl<T: Schedulable> Default for SchedulerQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+    struct Dummy {
+        class: ScheduleClass,
+        priority: Priority,
+        vruntime: AtomicU64,
+        affinity: usize,
+        queued: AtomicBool,
+    }
+
+    impl Schedulable for Dummy {
+        fn class(&self) -> ScheduleClass {
+            self.class
+        }
+        fn priority(&self) -> Priority {
+            self.priority
+        }
+        fn vruntime(&self) -> Vruntime {
+            Vruntime::new(self.vruntime.load(Ordering::Relaxed) as u128)
+        }
+        fn set_vruntime(&self, vruntime: Vruntime) {
+            self.vruntime
+                .store(vruntime.value() as u64, Ordering::Relaxed)
+        }
+        fn allowed(&self, hart: usize) -> bool {
+            self.affinity & 1 << hart != 0
+        }
+        fn swap_queued(&self, queued: bool) -> bool {
+            self.queued.swap(queued, Ordering::Relaxed)
+        }
+    }
+
+    fn dummy(class: ScheduleClass, vruntime: u64, priority: u32) -> Arc<Dummy> {
+        Arc::new(Dummy {
+            class,
+            priority: Priority::new(priority).unwrap(),
+            vruntime: AtomicU64::new(vruntime),
+            affinity: usize::MAX,
+            queued: AtomicBool::new(false),
+        })
+    }
+
+    fn normal(vruntime: u64) -> Arc<Dummy> {
+        dummy(ScheduleClass::Normal, vruntime, 1000)
+    }
+
+    #[test]
+    fn pop_order() {
+        let mut queue = SchedulerQueue::new();
+        let idle = dummy(ScheduleClass::Idle, 0, 1000);
+        let fifo_low = dummy(ScheduleClass::Fifo, 0, 10);
+        let fifo_high = dummy(ScheduleClass::Fifo, 0, 20);
+        let late = normal(300);
+        let early = normal(100);
+        for task in [&idle, &fifo_low, &late, &fifo_high, &early] {
+            queue.insert(task.clone());
+        }
+        assert_eq!(queue.count(), 5);
+        for task in [&fifo_high, &fifo_low, &early, &late, &idle] {
+            assert!(Arc::ptr_eq(&queue.pop().unwrap(), task));
+        }
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn fifo_keeps_arrival_order() {
+        let mut queue = SchedulerQueue::new();
+        let first = dummy(ScheduleClass::Fifo, 0, 10);
+        let second = dummy(ScheduleClass::Fifo, 0, 10);
+        queue.insert(first.clone());
+        queue.insert(second.clone());
+        assert!(Arc::ptr_eq(&queue.pop().unwrap(), &first));
+        assert!(Arc::ptr_eq(&queue.pop().unwrap(), &second));
+    }
+
+    #[test]
+    fn min_vruntime() {
+        let mut queue = SchedulerQueue::new();
+        assert_eq!(queue.min_vruntime(), Vruntime::new(0));
+        queue.insert(normal(500));
+        queue.insert(normal(700));
+        queue.pop().unwrap();
+        assert_eq!(queue.min_vruntime(), Vruntime::new(500));
+        queue.pop().unwrap();
+        assert_eq!(queue.min_vruntime(), Vruntime::new(700));
+        assert!(queue.pop().is_none());
+        assert_eq!(queue.min_vruntime(), Vruntime::new(700));
+        queue.insert(normal(600));
+        queue.pop().unwrap();
+        assert_eq!(queue.min_vruntime(), Vruntime::new(700));
+    }
+
+    #[test]
+    fn charge() {
+        let task = normal(0);
+        task.charge(Duration::from_millis(1));
+        assert_eq!(task.vruntime(), Vruntime::new(1000000));
+        let heavy = dummy(ScheduleClass::Normal, 0, 4000);
+        heavy.charge(Duration::from_millis(1));
+        assert_eq!(heavy.vruntime(), Vruntime::new(250000));
+        let fifo = dummy(ScheduleClass::Fifo, 0, 1000);
+        fifo.charge(Duration::from_millis(1));
+        assert_eq!(fifo.vruntime(), Vruntime::new(0));
+    }
+
+    #[test]
+    fn place() {
+        let mut queue = SchedulerQueue::new();
+        queue.insert(normal(10000000));
+        queue.pop().unwrap();
+        let credit = Duration::from_millis(5);
+        let sleeper = normal(0);
+        queue.place(&sleeper, credit);
+        assert_eq!(sleeper.vruntime(), Vruntime::new(5000000));
+        let runner = normal(12000000);
+        queue.place(&runner, credit);
+        assert_eq!(runner.vruntime(), Vruntime::new(12000000));
+        let idle = dummy(ScheduleClass::Idle, 0, 1000);
+        queue.place(&idle, credit);
+        assert_eq!(idle.vruntime(), Vruntime::new(0));
+    }
+
+    #[test]
+    fn steal() {
+        let mut queue = SchedulerQueue::new();
+        let pinned = Arc::new(Dummy {
+            class: ScheduleClass::Normal,
+            priority: Priority::DEFAULT,
+            vruntime: AtomicU64::new(900),
+            affinity: 1 << 0,
+            queued: AtomicBool::new(false),
+        });
+        let free = normal(100);
+        queue.insert(pinned.clone());
+        queue.insert(free.clone());
+        assert!(queue.stealable(1));
+        assert!(Arc::ptr_eq(&queue.steal(1).unwrap(), &free));
+        assert!(!queue.stealable(1));
+        assert!(queue.steal(1).is_none());
+        assert!(queue.stealable(0));
+        assert!(Arc::ptr_eq(&queue.steal(0).unwrap(), &pinned));
+        assert_eq!(queue.count(), 0);
+    }
+
+    #[test]
+    fn queued_once() {
+        let mut queue = SchedulerQueue::new();
+        let mut other = SchedulerQueue::new();
+        let fifo = dummy(ScheduleClass::Fifo, 0, 10);
+        let idle = dummy(ScheduleClass::Idle, 0, 1000);
+        let task = normal(100);
+        for x in [&fifo, &idle, &task] {
+            queue.insert(x.clone());
+            queue.insert(x.clone());
+            other.insert(x.clone());
+        }
+        assert_eq!(queue.count(), 3);
+        assert_eq!(other.count(), 0);
+        assert!(Arc::ptr_eq(&queue.pop().unwrap(), &fifo));
+        assert!(Arc::ptr_eq(&queue.steal(1).unwrap(), &task));
+        other.insert(task.clone());
+        other.insert(fifo.clone());
+        assert_eq!(other.count(), 2);
+        assert!(Arc::ptr_eq(&queue.pop().unwrap(), &idle));
+        assert!(queue.pop().is_none());
+    }
+}
@@ -11,4 +11,12 @@ fn main() {
         "../../../crates/nekos-initproc/target/riscv64gc-unknown-none-elf/debug/nekos-initproc"
     );
     // values: string
+
+    // initrd archive
+    let initrd = "";
+    // values: string, a cpio (newc) or ustar archive, or "" for none
+    if !initrd.is_empty() {
+        println!("cargo:rustc-cfg=memfs_initrd");
+        println!("cargo:rustc-env=memfs_initrd={}", initrd);
+    }
 }
@@ -1,6 +1,7 @@
+use alloc::boxed::Box;
 use core::future::Future;
 use core::pin::Pin;
-use core::task::Poll;
+use core::task::{Context, Poll};
 
 pub fn yield_now() -> impl Future<Output = ()> {
     struct Yield(bool);
@@ -18,3 +19,15 @@ pub fn yield_now() -> impl Future<Output = ()> {
     }
     Yield(false)
 }
+
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = futures::task::noop_waker();
+    let cx = &mut Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return output;
+        }
+        core::hint::spin_loop();
+    }
+}
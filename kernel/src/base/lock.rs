@@ -0,0 +1,83 @@
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::future::Future;
+use core::ops::{Deref, DerefMut};
+use core::task::{Poll, Waker};
+
+pub struct AsyncMutex<T: ?Sized> {
+    state: spin::Mutex<AsyncMutexState>,
+    value: UnsafeCell<T>,
+}
+
+struct AsyncMutexState {
+    locked: bool,
+    waiters: Vec<Waker>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for AsyncMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for AsyncMutex<T> {}
+
+impl<T> AsyncMutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: spin::Mutex::new(AsyncMutexState {
+                locked: false,
+                waiters: Vec::new(),
+            }),
+            value: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized> AsyncMutex<T> {
+    pub fn try_lock(&self) -> Option<AsyncMutexGuard<'_, T>> {
+        let mut state = self.state.lock();
+        if state.locked {
+            return None;
+        }
+        state.locked = true;
+        Some(AsyncMutexGuard { mutex: self })
+    }
+    pub fn lock(&self) -> impl Future<Output = AsyncMutexGuard<'_, T>> {
+        futures::future::poll_fn(move |cx| {
+            let mut state = self.state.lock();
+            if state.locked {
+                state.waiters.push(cx.waker().clone());
+                return Poll::Pending;
+            }
+            state.locked = true;
+            Poll::Ready(AsyncMutexGuard { mutex: self })
+        })
+    }
+}
+
+pub struct AsyncMutexGuard<'a, T: ?Sized> {
+    mutex: &'a AsyncMutex<T>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for AsyncMutexGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for AsyncMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for AsyncMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for AsyncMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        let waiters = {
+            let mut state = self.mutex.state.lock();
+            state.locked = false;
+            core::mem::take(&mut state.waiters)
+        };
+        waiters.into_iter().for_each(Waker::wake);
+    }
+}
@@ -2,4 +2,5 @@ pub mod cell;
 pub mod either;
 pub mod error;
 pub mod future;
+pub mod lock;
 pub mod thread;
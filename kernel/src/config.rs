@@ -20,10 +20,32 @@ pub const BACKTRACE: usize = 64;
 // memory
 pub const HEAP_SIZE: usize = 16 * 1024 * 1024;
 pub const STACK_SIZE: usize = 2 * 1024 * 1024;
+pub const FRAMES_ZEROED_POOL: usize = 256;
+pub const FRAMES_ZEROED_BATCH: usize = 16;
+
+// drivers
+pub const VIRTIO_QUEUE_SIZE: u16 = 128;
+pub const BLOCK_CACHE_CAPACITY: usize = 1024;
+
+// file system
+pub const PATH_MAX: usize = 4096;
+pub const FILE_IO_MAX: usize = 64 * 1024;
+pub const PAGE_CACHE_LAYOUT: MapLayout = MapLayout::new(4096, 4096).unwrap();
+pub const TMPFS_PAGE_LAYOUT: MapLayout = MapLayout::new(4096, 4096).unwrap();
+pub const TMPFS_FILE_MAX: u64 = 256 * 1024 * 1024;
+
+// random
+pub const RANDOM_GET_MAX: usize = 64 * 1024;
+pub const RANDOM_JITTER_ROUNDS: usize = 256;
+pub const RANDOM_RESEED_INTERVAL: Duration = Duration::from_secs(60);
 
 // process
 pub const PROCESS_RESERVE_HANDLES: usize = 65536;
+pub const PROCESS_ARGUMENTS_MAX: usize = 4 * 1024;
+pub const PROCESS_RANDOMIZED: bool = true;
 pub const THREAD_STACK_LAYOUT: MapLayout = MapLayout::new(16 * 1024, 4096).unwrap();
 
 // schedule
 pub const SCHEDULE_TIMESLICE: Duration = Duration::from_millis(10);
+pub const SCHEDULE_SLEEPER_CREDIT: Duration = Duration::from_millis(5);
+pub const INITPROC_EXIT_POLICY: InitprocExitPolicy = InitprocExitPolicy::PowerOff;
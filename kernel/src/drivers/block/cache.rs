@@ -0,0 +1,187 @@
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use base::lock::AsyncMutex;
+use drivers::block::{BlockDevice, BlockError};
+use spin::Lazy;
+
+pub const BLOCK_SIZE: usize = 4096;
+
+struct BlockCacheEntry {
+    device: Arc<dyn BlockDevice>,
+    data: Box<[u8]>,
+    len: usize,
+    dirty: bool,
+    tick: u64,
+}
+
+impl BlockCacheEntry {
+    async fn write_back(&mut self, block: u64) -> Result<(), BlockError> {
+        if self.dirty {
+            let sector = block * (BLOCK_SIZE / self.device.sector_size()) as u64;
+            self.device.write(sector, &self.data[..self.len]).await?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+}
+
+struct BlockCacheInner {
+    entries: BTreeMap<(usize, u64), BlockCacheEntry>,
+    lru: BTreeMap<u64, (usize, u64)>,
+    tick: u64,
+}
+
+pub struct BlockCache {
+    capacity: usize,
+    inner: AsyncMutex<BlockCacheInner>,
+}
+
+fn id(device: &Arc<dyn BlockDevice>) -> usize {
+    Arc::as_ptr(device) as *const () as usize
+}
+
+impl BlockCache {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity != 0);
+        Self {
+            capacity,
+            inner: AsyncMutex::new(BlockCacheInner {
+                entries: BTreeMap::new(),
+                lru: BTreeMap::new(),
+                tick: 0,
+            }),
+        }
+    }
+
+    async fn entry<'a>(
+        &self,
+        inner: &'a mut BlockCacheInner,
+        device: &Arc<dyn BlockDevice>,
+        block: u64,
+        fill: bool,
+    ) -> Result<&'a mut BlockCacheEntry, BlockError> {
+        use BlockError::*;
+        let key = (id(device), block);
+        if !inner.entries.contains_key(&key) {
+            let sector_size = device.sector_size();
+            ensure!(BLOCK_SIZE % sector_size == 0, NotSupported);
+            let total = device.capacity() * sector_size as u64;
+            let start = block.checked_mul(BLOCK_SIZE as u64).ok_or(OutOfRange)?;
+            ensure!(start < total, OutOfRange);
+            let len = u64::min(BLOCK_SIZE as u64, total - start) as usize;
+            while inner.entries.len() >= self.capacity {
+                let (tick, victim) = inner.lru.first_key_value().map(|(&t, &k)| (t, k)).unwrap();
+                let entry = inner.entries.get_mut(&victim).unwrap();
+                entry.write_back(victim.1).await?;
+                inner.lru.remove(&tick);
+                inner.entries.remove(&victim);
+            }
+            let mut data = vec![0u8; BLOCK_SIZE].into_boxed_slice();
+            if fill {
+                device
+                    .read(start / sector_size as u64, &mut data[..len])
+                    .await?;
+            }
+            inner.entries.insert(
+                key,
+                BlockCacheEntry {
+                    device: device.clone(),
+                    data,
+                    len,
+                    dirty: false,
+                    tick: 0,
+                },
+            );
+        }
+        inner.tick += 1;
+        let tick = inner.tick;
+        let entry = inner.entries.get_mut(&key).unwrap();
+        inner.lru.remove(&entry.tick);
+        inner.lru.insert(tick, key);
+        entry.tick = tick;
+        Ok(entry)
+    }
+
+    pub async fn read(
+        &self,
+        device: &Arc<dyn BlockDevice>,
+        mut offset: u64,
+        mut buffer: &mut [u8],
+    ) -> Result<(), BlockError> {
+        let mut inner = self.inner.lock().await;
+        while !buffer.is_empty() {
+            let block = offset / BLOCK_SIZE as u64;
+            let inside = (offset % BLOCK_SIZE as u64) as usize;
+            let entry = self.entry(&mut inner, device, block, true).await?;
+            ensure!(inside < entry.len, BlockError::OutOfRange);
+            let len = usize::min(buffer.len(), entry.len - inside);
+            buffer[..len].copy_from_slice(&entry.data[inside..inside + len]);
+            offset += len as u64;
+            buffer = &mut buffer[len..];
+        }
+        Ok(())
+    }
+
+    pub async fn write(
+        &self,
+        device: &Arc<dyn BlockDevice>,
+        mut offset: u64,
+        mut buffer: &[u8],
+    ) -> Result<(), BlockError> {
+        ensure!(!device.read_only(), BlockError::ReadOnly);
+        let mut inner = self.inner.lock().await;
+        while !buffer.is_empty() {
+            let block = offset / BLOCK_SIZE as u64;
+            let inside = (offset % BLOCK_SIZE as u64) as usize;
+            let fill = inside != 0 || buffer.len() < BLOCK_SIZE;
+            let entry = self.entry(&mut inner, device, block, fill).await?;
+            ensure!(inside < entry.len, BlockError::OutOfRange);
+            let len = usize::min(buffer.len(), entry.len - inside);
+            entry.data[inside..inside + len].copy_from_slice(&buffer[..len]);
+            entry.dirty = true;
+            offset += len as u64;
+            buffer = &buffer[len..];
+        }
+        Ok(())
+    }
+
+    pub async fn sync(&self, device: Option<&Arc<dyn BlockDevice>>) -> Result<(), BlockError> {
+        let mut inner = self.inner.lock().await;
+        let mut devices = BTreeMap::new();
+        for (&(owner, block), entry) in inner.entries.iter_mut() {
+            if device.map_or(false, |device| id(device) != owner) {
+                continue;
+            }
+            if entry.dirty {
+                entry.write_back(block).await?;
+                devices.insert(owner, entry.device.clone());
+            }
+        }
+        drop(inner);
+        for device in devices.values() {
+            device.flush().await?;
+        }
+        Ok(())
+    }
+
+    pub async fn invalidate(&self, device: &Arc<dyn BlockDevice>) -> Result<(), BlockError> {
+        self.sync(Some(device)).await?;
+        let mut inner = self.inner.lock().await;
+        let owner = id(device);
+        let keys = inner
+            .entries
+            .range((owner, 0)..=(owner, u64::MAX))
+            .map(|(&key, entry)| (key, entry.tick))
+            .collect::<Vec<_>>();
+        for (key, tick) in keys {
+            inner.entries.remove(&key);
+            inner.lru.remove(&tick);
+        }
+        Ok(())
+    }
+}
+
+pub fn cache() -> &'static BlockCache {
+    static CACHE: Lazy<BlockCache> = Lazy::new(|| BlockCache::new(config::BLOCK_CACHE_CAPACITY));
+    &CACHE
+}
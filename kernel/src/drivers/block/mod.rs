@@ -0,0 +1,50 @@
+pub mod cache;
+pub mod partition;
+pub mod ramdisk;
+
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use spin::Mutex;
+
+#[derive(Debug)]
+pub enum BlockError {
+    NotSupported,
+    BadBuffer,
+    OutOfRange,
+    ReadOnly,
+    IOError,
+}
+
+#[async_trait::async_trait]
+pub trait BlockDevice: Send + Sync {
+    fn sector_size(&self) -> usize;
+    fn capacity(&self) -> u64;
+    fn read_only(&self) -> bool;
+    async fn read(&self, sector: u64, buffer: &mut [u8]) -> Result<(), BlockError>;
+    async fn write(&self, sector: u64, buffer: &[u8]) -> Result<(), BlockError>;
+    async fn flush(&self) -> Result<(), BlockError>;
+}
+
+static DEVICES: Mutex<BTreeMap<String, Arc<dyn BlockDevice>>> = Mutex::new(BTreeMap::new());
+
+pub fn register(name: &str, device: Arc<dyn BlockDevice>) {
+    info!(
+        "block device {}: {} sectors of {} bytes",
+        name,
+        device.capacity(),
+        device.sector_size()
+    );
+    DEVICES.lock().insert(name.to_string(), device);
+}
+
+pub fn device(name: &str) -> Option<Arc<dyn BlockDevice>> {
+    DEVICES.lock().get(name).cloned()
+}
+
+pub fn devices() -> Vec<(String, Arc<dyn BlockDevice>)> {
+    DEVICES
+        .lock()
+        .iter()
+        .map(|(name, device)| (name.clone(), device.clone()))
+        .collect()
+}
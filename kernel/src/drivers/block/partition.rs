@@ -0,0 +1,279 @@
+use crate::prelude::*;
+use alloc::format;
+use drivers::block::{BlockDevice, BlockError};
+use fs::utils::{u16_at, u32_at, u64_at};
+
+#[derive(Debug)]
+pub enum PartitionError {
+    NoTable,
+    BadTable,
+    IOError,
+}
+
+impl From<BlockError> for PartitionError {
+    fn from(_: BlockError) -> Self {
+        Self::IOError
+    }
+}
+
+pub struct Partition {
+    device: Arc<dyn BlockDevice>,
+    start: u64,
+    sectors: u64,
+}
+
+impl Partition {
+    pub fn new(device: Arc<dyn BlockDevice>, start: u64, sectors: u64) -> Option<Self> {
+        let end = start.checked_add(sectors)?;
+        if sectors == 0 || end > device.capacity() {
+            return None;
+        }
+        Some(Self {
+            device,
+            start,
+            sectors,
+        })
+    }
+    fn translate(&self, sector: u64, len: usize) -> Result<u64, BlockError> {
+        use BlockError::*;
+        let sector_size = self.device.sector_size();
+        ensure!(len % sector_size == 0, BadBuffer);
+        let end = sector
+            .checked_add((len / sector_size) as u64)
+            .ok_or(OutOfRange)?;
+        ensure!(end <= self.sectors, OutOfRange);
+        Ok(self.start + sector)
+    }
+}
+
+#[async_trait::async_trait]
+impl BlockDevice for Partition {
+    fn sector_size(&self) -> usize {
+        self.device.sector_size()
+    }
+    fn capacity(&self) -> u64 {
+        self.sectors
+    }
+    fn read_only(&self) -> bool {
+        self.device.read_only()
+    }
+    async fn read(&self, sector: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        let sector = self.translate(sector, buffer.len())?;
+        self.device.read(sector, buffer).await
+    }
+    async fn write(&self, sector: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        let sector = self.translate(sector, buffer.len())?;
+        self.device.write(sector, buffer).await
+    }
+    async fn flush(&self) -> Result<(), BlockError> {
+        self.device.flush().await
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut value = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            value = if value & 1 != 0 {
+                0xedb88320 ^ (value >> 1)
+            } else {
+                value >> 1
+            };
+            j += 1;
+        }
+        table[i] = value;
+        i += 1;
+    }
+    table
+};
+
+fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    data.iter().fold(crc, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !crc32_update(!0u32, data)
+}
+
+async fn read(
+    device: &Arc<dyn BlockDevice>,
+    sector: u64,
+    count: usize,
+) -> Result<Vec<u8>, PartitionError> {
+    ensure!(
+        sector
+            .checked_add(count as u64)
+            .map_or(false, |end| end <= device.capacity()),
+        PartitionError::BadTable
+    );
+    let mut buffer = vec![0u8; count * device.sector_size()];
+    device.read(sector, &mut buffer).await?;
+    Ok(buffer)
+}
+
+async fn gpt_at(
+    device: &Arc<dyn BlockDevice>,
+    lba: u64,
+) -> Result<Vec<(u64, u64)>, PartitionError> {
+    use PartitionError::*;
+    let sector_size = device.sector_size();
+    let header = read(device, lba, 1).await?;
+    ensure!(&header[0..8] == b"EFI PART", BadTable);
+    let header_size = u32_at(&header, 12) as usize;
+    ensure!(92 <= header_size && header_size <= sector_size, BadTable);
+    let mut copy = header[..header_size].to_vec();
+    copy[16..20].fill(0);
+    ensure!(crc32(&copy) == u32_at(&header, 16), BadTable);
+    ensure!(u64_at(&header, 24) == lba, BadTable);
+    let first_usable = u64_at(&header, 40);
+    let last_usable = u64_at(&header, 48);
+    let entries_lba = u64_at(&header, 72);
+    let entries = u32_at(&header, 80) as usize;
+    let entry_size = u32_at(&header, 84) as usize;
+    ensure!(
+        (128..=4096).contains(&entry_size) && entry_size % 8 == 0 && entries <= 1024,
+        BadTable
+    );
+    let mut remaining = entries * entry_size;
+    let mut sector = entries_lba;
+    let mut crc = !0u32;
+    let mut pending = Vec::with_capacity(entry_size + sector_size);
+    let mut ranges = Vec::new();
+    while remaining != 0 {
+        let data = read(device, sector, 1).await?;
+        let len = usize::min(remaining, sector_size);
+        crc = crc32_update(crc, &data[..len]);
+        pending.extend_from_slice(&data[..len]);
+        while pending.len() >= entry_size {
+            let entry = &pending[..entry_size];
+            if entry[0..16].iter().any(|&x| x != 0) {
+                ranges.push(Some((u64_at(entry, 32), u64_at(entry, 40))));
+            } else {
+                ranges.push(None);
+            }
+            pending.drain(..entry_size);
+        }
+        remaining -= len;
+        sector = sector.checked_add(1).ok_or(BadTable)?;
+    }
+    ensure!(!crc == u32_at(&header, 88), BadTable);
+    let mut partitions = Vec::new();
+    for range in ranges {
+        let (first, last) = match range {
+            Some(range) => range,
+            None => {
+                partitions.push((0, 0));
+                continue;
+            }
+        };
+        ensure!(
+            first_usable <= first && first <= last && last <= last_usable,
+            BadTable
+        );
+        partitions.push((first, last - first + 1));
+    }
+    Ok(partitions)
+}
+
+async fn gpt(device: &Arc<dyn BlockDevice>) -> Result<Vec<(u64, u64)>, PartitionError> {
+    match gpt_at(device, 1).await {
+        Ok(partitions) => Ok(partitions),
+        Err(PartitionError::BadTable) => {
+            warn!("the primary GPT header is broken, trying the backup one");
+            gpt_at(device, device.capacity() - 1).await
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn superfloppy(sector: &[u8]) -> bool {
+    let bytes_per_sector = u16_at(sector, 11);
+    let sectors_per_cluster = sector[13];
+    (sector[0] == 0xe9 || (sector[0] == 0xeb && sector[2] == 0x90))
+        && [512, 1024, 2048, 4096].contains(&bytes_per_sector)
+        && sectors_per_cluster.is_power_of_two()
+        && u16_at(sector, 14) != 0
+        && sector[16] != 0
+}
+
+fn extended(kind: u8) -> bool {
+    matches!(kind, 0x05 | 0x0f | 0x85)
+}
+
+async fn mbr(
+    device: &Arc<dyn BlockDevice>,
+    sector: &[u8],
+) -> Result<Vec<(u64, u64)>, PartitionError> {
+    use PartitionError::*;
+    let mut partitions = Vec::new();
+    let mut logicals = Vec::new();
+    for entry in sector[446..510].chunks_exact(16) {
+        let kind = entry[4];
+        let start = u32_at(entry, 8) as u64;
+        let sectors = u32_at(entry, 12) as u64;
+        if kind == 0 || sectors == 0 {
+            partitions.push((0, 0));
+            continue;
+        }
+        ensure!(entry[0] & 0x7f == 0, BadTable);
+        partitions.push((start, sectors));
+        if extended(kind) {
+            let mut ebr = start;
+            for _ in 0..128 {
+                let sector = read(device, ebr, 1).await?;
+                ensure!(sector[510..512] == [0x55, 0xaa], BadTable);
+                let this = &sector[446..462];
+                let next = &sector[462..478];
+                if this[4] != 0 {
+                    logicals.push((ebr + u32_at(this, 8) as u64, u32_at(this, 12) as u64));
+                }
+                if next[4] == 0 || !extended(next[4]) {
+                    break;
+                }
+                ebr = start + u32_at(next, 8) as u64;
+            }
+            *partitions.last_mut().unwrap() = (0, 0);
+        }
+    }
+    partitions.extend(logicals);
+    Ok(partitions)
+}
+
+pub async fn scan(device: &Arc<dyn BlockDevice>) -> Result<Vec<Option<Partition>>, PartitionError> {
+    use PartitionError::*;
+    let sector = read(device, 0, 1).await?;
+    ensure!(sector[510..512] == [0x55, 0xaa], NoTable);
+    let protective = sector[446..510]
+        .chunks_exact(16)
+        .any(|entry| entry[4] == 0xee);
+    let ranges = if protective {
+        gpt(device).await?
+    } else {
+        ensure!(!superfloppy(&sector), NoTable);
+        mbr(device, &sector).await?
+    };
+    Ok(ranges
+        .into_iter()
+        .map(|(start, sectors)| Partition::new(device.clone(), start, sectors))
+        .collect())
+}
+
+pub async fn register(name: &str, device: &Arc<dyn BlockDevice>) {
+    match scan(device).await {
+        Ok(partitions) => {
+            for (i, partition) in partitions.into_iter().enumerate() {
+                if let Some(partition) = partition {
+                    let name = format!("{}{}", name, i + 1);
+                    drivers::block::register(&name, Arc::new(partition));
+                }
+            }
+        }
+        Err(PartitionError::NoTable) => (),
+        Err(e) => warn!("failed to scan partitions of {}, reason = {:?}", name, e),
+    }
+}
@@ -0,0 +1,65 @@
+use crate::prelude::*;
+use drivers::block::{BlockDevice, BlockError};
+use spin::RwLock;
+
+pub const SECTOR_SIZE: usize = 512;
+
+pub struct Ramdisk {
+    data: RwLock<Box<[u8]>>,
+    read_only: bool,
+}
+
+impl Ramdisk {
+    pub fn new(sectors: u64) -> Self {
+        Self {
+            data: RwLock::new(vec![0u8; sectors as usize * SECTOR_SIZE].into_boxed_slice()),
+            read_only: false,
+        }
+    }
+    pub fn from_bytes(data: Box<[u8]>, read_only: bool) -> Option<Self> {
+        if data.len() % SECTOR_SIZE != 0 {
+            return None;
+        }
+        Some(Self {
+            data: RwLock::new(data),
+            read_only,
+        })
+    }
+    fn range(&self, sector: u64, len: usize) -> Result<core::ops::Range<usize>, BlockError> {
+        use BlockError::*;
+        ensure!(len % SECTOR_SIZE == 0, BadBuffer);
+        let start = (sector as usize)
+            .checked_mul(SECTOR_SIZE)
+            .ok_or(OutOfRange)?;
+        let end = start.checked_add(len).ok_or(OutOfRange)?;
+        ensure!(end <= self.data.read().len(), OutOfRange);
+        Ok(start..end)
+    }
+}
+
+#[async_trait::async_trait]
+impl BlockDevice for Ramdisk {
+    fn sector_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+    fn capacity(&self) -> u64 {
+        (self.data.read().len() / SECTOR_SIZE) as u64
+    }
+    fn read_only(&self) -> bool {
+        self.read_only
+    }
+    async fn read(&self, sector: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        let range = self.range(sector, buffer.len())?;
+        buffer.copy_from_slice(&self.data.read()[range]);
+        Ok(())
+    }
+    async fn write(&self, sector: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        ensure!(!self.read_only, BlockError::ReadOnly);
+        let range = self.range(sector, buffer.len())?;
+        self.data.write()[range].copy_from_slice(buffer);
+        Ok(())
+    }
+    async fn flush(&self) -> Result<(), BlockError> {
+        Ok(())
+    }
+}
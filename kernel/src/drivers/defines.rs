@@ -65,3 +65,7 @@ impl<T: ?Sized> AsRawDma for DmaBox<T> {
         RawDmaMut(paddr, size)
     }
 }
+
+pub trait InterruptHandler: Send + Sync {
+    fn interrupt(&self);
+}
@@ -1,5 +1,12 @@
 use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use core::sync::atomic::{AtomicU64, Ordering};
+use drivers::block::BlockDevice;
 use drivers::virtio::DeviceType;
+use drivers::virtio_blk::Blk;
+use drivers::virtio_rng::Rng;
+use rt::time::Instant;
 use spin::Mutex;
 
 #[derive(Debug, Clone)]
@@ -10,6 +17,9 @@ pub struct Record {
 }
 
 static RECORDS: Mutex<Vec<Record>> = Mutex::new(Vec::new());
+static HANDLERS: Mutex<BTreeMap<usize, Arc<dyn InterruptHandler>>> = Mutex::new(BTreeMap::new());
+static RNG: Mutex<Option<Arc<Rng>>> = Mutex::new(None);
+static RESEED: AtomicU64 = AtomicU64::new(0);
 
 pub fn register(addr: PAddr, size: usize, int: Vec<usize>) {
     RECORDS.lock().push(Record { addr, size, int });
@@ -17,6 +27,7 @@ pub fn register(addr: PAddr, size: usize, int: Vec<usize>) {
 
 pub fn init_global() {
     let records = RECORDS.lock().clone();
+    let mut blks = 0;
     for record in records {
         let addr = record.addr.to_usize() as *mut u8;
         let size = record.size;
@@ -24,9 +35,33 @@ pub fn init_global() {
         match mmio {
             Ok(mmio) => match mmio.device() {
                 DeviceType::Invalid => (),
-                DeviceType::Block => {
-                    info!("find a block device");
-                }
+                DeviceType::Block => match Blk::new(mmio) {
+                    Ok(blk) => {
+                        info!("find a block device");
+                        let blk = Arc::new(blk);
+                        listen(&record.int, blk.clone());
+                        let name = format!("vd{}", (b'a' + blks) as char);
+                        let device: Arc<dyn BlockDevice> = blk;
+                        drivers::block::register(&name, device.clone());
+                        base::future::block_on(drivers::block::partition::register(&name, &device));
+                        blks += 1;
+                    }
+                    Err(e) => warn!("failed to initialize the block device, reason = {:?}", e),
+                },
+                DeviceType::EntropySource => match Rng::new(mmio) {
+                    Ok(rng) => {
+                        info!("find an entropy source");
+                        let rng = Arc::new(rng);
+                        listen(&record.int, rng.clone());
+                        let mut seed = [0u8; 64];
+                        match base::future::block_on(rng.read(&mut seed)) {
+                            Ok(len) => rt::random::feed(&seed[..len]),
+                            Err(e) => warn!("failed to read the entropy source, reason = {:?}", e),
+                        }
+                        *RNG.lock() = Some(rng);
+                    }
+                    Err(e) => warn!("failed to initialize the entropy source, reason = {:?}", e),
+                },
                 fallback => {
                     warn!("no driver for the MMIO device {:?}", fallback);
                 }
@@ -34,4 +69,53 @@ pub fn init_global() {
             Err(e) => warn!("failed to acknowledge the device, reason = {:?}", e),
         }
     }
+    if RNG.lock().is_none() {
+        warn!(
+            "no hardware entropy source is found, random numbers are seeded from timer jitter only"
+        );
+    }
+}
+
+pub fn reseed() {
+    let rng = match RNG.lock().clone() {
+        Some(rng) => rng,
+        None => return,
+    };
+    let now = Instant::now();
+    let deadline = RESEED.load(Ordering::Relaxed);
+    let next = (now + config::RANDOM_RESEED_INTERVAL).value();
+    if now.value() < deadline
+        || RESEED
+            .compare_exchange(deadline, next, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+    {
+        return;
+    }
+    sched::scheduler::detach(async move {
+        let mut seed = [0u8; 32];
+        match rng.read(&mut seed).await {
+            Ok(len) => rt::random::feed(&seed[..len]),
+            Err(e) => warn!("failed to reseed from the entropy source, reason = {:?}", e),
+        }
+    });
+}
+
+fn listen(int: &[usize], handler: Arc<dyn InterruptHandler>) {
+    let mut handlers = HANDLERS.lock();
+    for &value in int {
+        handlers.insert(value, handler.clone());
+        P::interrupt_enable(value);
+    }
+}
+
+pub fn interrupt() {
+    while let Some(value) = P::interrupt_claim() {
+        rt::random::stir(value as u64);
+        let handler = HANDLERS.lock().get(&value).cloned();
+        match handler {
+            Some(handler) => handler.interrupt(),
+            None => warn!("no handler for the interrupt {}", value),
+        }
+        P::interrupt_complete(value);
+    }
 }
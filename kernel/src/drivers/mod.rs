@@ -1,4 +1,6 @@
+pub mod block;
 pub mod defines;
 pub mod manager;
 pub mod virtio;
 pub mod virtio_blk;
+pub mod virtio_rng;
@@ -36,14 +36,21 @@ impl MMIO {
             .write(self.regs.status.read() | DeviceStatus::DRIVER);
     }
 
-    pub fn init_features_ok(&mut self, features: u64) {
-        self.regs.device_features_sel.write(0);
+    pub fn init_features_ok(&mut self, features: u64) -> bool {
+        self.regs.driver_features_sel.write(0);
         self.regs.driver_features.write(features as u32);
-        self.regs.device_features_sel.write(1);
+        self.regs.driver_features_sel.write(1);
         self.regs.driver_features.write((features >> 32) as u32);
         self.regs
             .status
             .write(self.regs.status.read() | DeviceStatus::FEATURES_OK);
+        self.regs.status.read() & DeviceStatus::FEATURES_OK != DeviceStatus::NONE
+    }
+
+    pub fn init_failed(&mut self) {
+        self.regs
+            .status
+            .write(self.regs.status.read() | DeviceStatus::FAILED);
     }
 
     pub fn init_driver_ok(&mut self) {
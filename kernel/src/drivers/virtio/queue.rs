@@ -15,6 +15,7 @@ type DmaBox<T> = Box<T, DmaAllocator>;
 #[derive(Debug)]
 pub enum VirtQueueError {
     NotAvailable,
+    BadSize,
     Underflow,
     Overflow,
 }
@@ -42,9 +43,12 @@ impl VirtQueue {
     pub fn new(mmio: &mut MMIO, index: u32, size: u16) -> Result<Self, VirtQueueError> {
         use VirtQueueError::*;
         mmio.queue_select(index);
-        if mmio.queue_max_size() as u16 == 0 || !size.is_power_of_two() {
+        if mmio.queue_max_size() == 0 {
             return Err(NotAvailable);
         }
+        if !size.is_power_of_two() || size as u32 > mmio.queue_max_size() {
+            return Err(BadSize);
+        }
         let desc: DmaBox<[VolCell<Desc>]> = unsafe { new_zeroed_unsize(size as usize) };
         let mut avail: DmaBox<Avail> = unsafe { new_zeroed_unsize(size as usize) };
         let mut used: DmaBox<Used> = unsafe { new_zeroed_unsize(size as usize) };
@@ -152,7 +156,7 @@ impl Avail {
         let avail_index = self.idx.read();
         self.ring[avail_index as usize & (self.ring.len() - 1)].write(x);
         fence(Ordering::SeqCst);
-        self.idx.write(avail_index + 1);
+        self.idx.write(avail_index.wrapping_add(1));
     }
 }
 
@@ -161,7 +165,14 @@ impl Avail {
 pub struct Used {
     pub flags: VolCell<u16>,
     pub idx: VolRCell<u16>,
-    pub ring: [VolRCell<(u16, u16, u32)>],
+    pub ring: [VolRCell<UsedElem>],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UsedElem {
+    pub id: u32,
+    pub len: u32,
 }
 
 impl Used {
@@ -170,8 +181,8 @@ impl Used {
             return None;
         }
         fence(Ordering::SeqCst);
-        let (id, _, len) = self.ring[*last as usize].read();
-        *last = (*last + 1) & (self.ring.len() as u16 - 1);
+        let UsedElem { id, len } = self.ring[*last as usize & (self.ring.len() - 1)].read();
+        *last = last.wrapping_add(1);
         Some((id as u16, len))
     }
 }
@@ -1,39 +1,53 @@
 use crate::prelude::*;
 use alloc::collections::BTreeMap;
 use base::cell::VolCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use drivers::block::{BlockDevice, BlockError};
 use drivers::virtio::mmio::MMIO;
 use drivers::virtio::queue::VirtQueue;
 use drivers::virtio::DeviceType;
 use mem::dma::DmaAllocator;
+use spin::Mutex;
 
 type DmaBox<T> = Box<T, DmaAllocator>;
 
 pub const QUEUE: u32 = 0;
+pub const SECTOR_SIZE: usize = 512;
+
+const F_SIZE_MAX: u64 = 1 << 1;
+const F_SEG_MAX: u64 = 1 << 2;
+const F_RO: u64 = 1 << 5;
+const F_BLK_SIZE: u64 = 1 << 6;
+const F_FLUSH: u64 = 1 << 9;
+const F_DISCARD: u64 = 1 << 13;
+const F_WRITE_ZEROES: u64 = 1 << 14;
+const F_VERSION_1: u64 = 1 << 32;
 
 #[derive(Debug)]
 pub enum BlkError {
     NotABlk,
     NotSupported,
     BadConfig,
+    BadFeatures,
+    BadBuffer,
+    OutOfRange,
+    ReadOnly,
+    IOError,
 }
 
-enum BlkSave {
-    Read(
-        DmaBox<BlkRequestHeader>,
-        DmaBox<BlkStatus>,
-        DmaBox<[u8; 512]>,
-    ),
-    Write(
-        DmaBox<BlkRequestHeader>,
-        DmaBox<BlkStatus>,
-        DmaBox<[u8; 512]>,
-    ),
+struct BlkSave {
+    _request: DmaBox<BlkRequestHeader>,
+    status: DmaBox<BlkStatus>,
+    _keep: Option<Arc<dyn Send + Sync>>,
+    state: BlkState,
 }
 
-#[derive(Debug)]
-pub enum BlkReturn {
-    Read(BlkStatus, DmaBox<[u8; 512]>),
-    Write(BlkStatus, DmaBox<[u8; 512]>),
+enum BlkState {
+    Pending(Option<Waker>),
+    Done,
+    Abandoned,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -48,9 +62,22 @@ pub enum BlkStatus {
 }
 
 pub struct Blk {
+    inner: Mutex<BlkInner>,
+    features: u64,
+    capacity: u64,
+    block_size: usize,
+    size_max: usize,
+    seg_max: usize,
+    max_discard_sectors: u32,
+    max_write_zeroes_sectors: u32,
+    write_zeroes_may_unmap: bool,
+}
+
+struct BlkInner {
     mmio: MMIO,
     queue: VirtQueue,
     saves: BTreeMap<BlkToken, BlkSave>,
+    waiters: Vec<Waker>,
 }
 
 impl Blk {
@@ -62,90 +89,448 @@ impl Blk {
         if config_raw.len() < core::mem::size_of::<BlkConfig>() {
             return Err(BlkError::BadConfig);
         }
-        let _config = unsafe { &mut *(mmio.config_data().as_mut_ptr() as *mut BlkConfig) };
+        let config = unsafe { &mut *(mmio.config_data().as_mut_ptr() as *mut BlkConfig) };
         mmio.init_ack();
         mmio.init_driver();
-        mmio.init_features_ok(0);
-        let queue = VirtQueue::new(&mut mmio, QUEUE, 16).unwrap();
-        mmio.init_driver_ok();
-        Ok(Blk {
-            mmio,
-            queue,
-            saves: BTreeMap::new(),
-        })
+        let features = mmio.features()
+            & (F_VERSION_1
+                | F_SIZE_MAX
+                | F_SEG_MAX
+                | F_RO
+                | F_BLK_SIZE
+                | F_FLUSH
+                | F_DISCARD
+                | F_WRITE_ZEROES);
+        if !mmio.init_features_ok(features) {
+            mmio.init_failed();
+            return Err(BlkError::BadFeatures);
+        }
+        mmio.queue_select(QUEUE);
+        let size = u32::min(config::VIRTIO_QUEUE_SIZE as u32, mmio.queue_max_size());
+        if size < 4 {
+            return Err(BlkError::NotSupported);
+        }
+        let size = 1u16 << (31 - size.leading_zeros());
+        let queue = VirtQueue::new(&mut mmio, QUEUE, size).map_err(|_| BlkError::NotSupported)?;
+        let limit = u32::MAX as usize / SECTOR_SIZE * SECTOR_SIZE;
+        let size_max = if features & F_SIZE_MAX != 0 {
+            let size_max = config.size_max.read() as usize / SECTOR_SIZE * SECTOR_SIZE;
+            size_max.clamp(SECTOR_SIZE, limit)
+        } else {
+            limit
+        };
+        let seg_max = if features & F_SEG_MAX != 0 {
+            config.seg_max.read() as usize
+        } else {
+            usize::MAX
+        };
+        let seg_max = seg_max.clamp(1, size as usize - 2);
+        let block_size = if features & F_BLK_SIZE != 0 {
+            config.blk_size.read() as usize
+        } else {
+            SECTOR_SIZE
+        };
+        if !block_size.is_power_of_two() || block_size < SECTOR_SIZE {
+            return Err(BlkError::BadConfig);
+        }
+        let blk = Blk {
+            features,
+            capacity: config.capacity.read(),
+            block_size,
+            size_max,
+            seg_max,
+            max_discard_sectors: config.max_discard_sectors.read().max(1),
+            max_write_zeroes_sectors: config.max_write_zeroes_sectors.read().max(1),
+            write_zeroes_may_unmap: config.write_zeroes_may_unmap.read() != 0,
+            inner: Mutex::new(BlkInner {
+                mmio,
+                queue,
+                saves: BTreeMap::new(),
+                waiters: Vec::new(),
+            }),
+        };
+        blk.inner.lock().mmio.init_driver_ok();
+        Ok(blk)
     }
 
-    pub fn interrupt_ack(&mut self) {
-        self.mmio.interrupt_ack();
+    pub fn capacity(&self) -> u64 {
+        self.capacity
     }
 
-    pub fn read(&mut self, sector: u64, buffer: DmaBox<[u8; 512]>) -> Result<BlkToken, BlkError> {
-        self.mmio.queue_lock(QUEUE);
-        let request = Box::new_in(
-            BlkRequestHeader::new(BlkRequestType::In, sector),
-            DmaAllocator,
-        );
-        let status = Box::new_in(BlkStatus::Ok, DmaAllocator);
-        let idx = self
-            .queue
-            .push(
-                &[request.as_raw_dma_ref()],
-                &[buffer.as_raw_dma_mut(), status.as_raw_dma_mut()],
-            )
-            .unwrap();
-        let token = BlkToken(idx);
-        self.saves
-            .insert(token, BlkSave::Read(request, status, buffer));
-        self.mmio.queue_unlock(QUEUE);
-        self.mmio.queue_notify(QUEUE);
-        Ok(token)
+    pub fn block_size(&self) -> usize {
+        self.block_size
     }
 
-    pub fn write(&mut self, sector: u64, buffer: DmaBox<[u8; 512]>) -> Result<BlkToken, BlkError> {
-        self.mmio.queue_lock(QUEUE);
-        let request = Box::new_in(
-            BlkRequestHeader::new(BlkRequestType::Out, sector),
-            DmaAllocator,
-        );
+    pub fn read_only(&self) -> bool {
+        self.features & F_RO != 0
+    }
+
+    pub fn support_flush(&self) -> bool {
+        self.features & F_FLUSH != 0
+    }
+
+    pub fn support_discard(&self) -> bool {
+        self.features & F_DISCARD != 0
+    }
+
+    pub fn support_write_zeroes(&self) -> bool {
+        self.features & F_WRITE_ZEROES != 0
+    }
+
+    pub async fn read_sectors(
+        &self,
+        sector: u64,
+        buffer: DmaBox<[u8]>,
+    ) -> Result<DmaBox<[u8]>, BlkError> {
+        let buffer = Arc::new(buffer);
+        let RawDmaMut(paddr, size) = buffer.as_raw_dma_mut();
+        self.request(
+            BlkRequestType::In,
+            sector,
+            &[(paddr, size)],
+            Some(buffer.clone()),
+        )
+        .await?;
+        Ok(Arc::try_unwrap(buffer).ok().unwrap())
+    }
+
+    pub async fn write_sectors(
+        &self,
+        sector: u64,
+        buffer: DmaBox<[u8]>,
+    ) -> Result<DmaBox<[u8]>, BlkError> {
+        let buffer = Arc::new(buffer);
+        let RawDmaRef(paddr, size) = buffer.as_raw_dma_ref();
+        self.request(
+            BlkRequestType::Out,
+            sector,
+            &[(paddr, size)],
+            Some(buffer.clone()),
+        )
+        .await?;
+        Ok(Arc::try_unwrap(buffer).ok().unwrap())
+    }
+
+    pub async fn read_vectored(
+        &self,
+        sector: u64,
+        buffers: Vec<DmaBox<[u8]>>,
+    ) -> Result<Vec<DmaBox<[u8]>>, BlkError> {
+        let buffers = Arc::new(buffers);
+        let segments = buffers
+            .iter()
+            .map(|buffer| {
+                let RawDmaMut(paddr, size) = buffer.as_raw_dma_mut();
+                (paddr, size)
+            })
+            .collect::<Vec<_>>();
+        self.request(BlkRequestType::In, sector, &segments, Some(buffers.clone()))
+            .await?;
+        Ok(Arc::try_unwrap(buffers).ok().unwrap())
+    }
+
+    pub async fn write_vectored(
+        &self,
+        sector: u64,
+        buffers: Vec<DmaBox<[u8]>>,
+    ) -> Result<Vec<DmaBox<[u8]>>, BlkError> {
+        let buffers = Arc::new(buffers);
+        let segments = buffers
+            .iter()
+            .map(|buffer| {
+                let RawDmaRef(paddr, size) = buffer.as_raw_dma_ref();
+                (paddr, size)
+            })
+            .collect::<Vec<_>>();
+        self.request(
+            BlkRequestType::Out,
+            sector,
+            &segments,
+            Some(buffers.clone()),
+        )
+        .await?;
+        Ok(Arc::try_unwrap(buffers).ok().unwrap())
+    }
+
+    pub async fn flush(&self) -> Result<(), BlkError> {
+        if !self.support_flush() {
+            return Ok(());
+        }
+        self.execute(BlkRequestType::Flush, 0, &[], None).await
+    }
+
+    pub async fn discard(&self, sector: u64, count: u64) -> Result<(), BlkError> {
+        use BlkError::*;
+        ensure!(self.support_discard(), NotSupported);
+        ensure!(!self.read_only(), ReadOnly);
+        self.check_range(sector, count)?;
+        self.ranged(
+            BlkRequestType::Discard,
+            sector,
+            count,
+            self.max_discard_sectors,
+            0,
+        )
+        .await
+    }
+
+    pub async fn write_zeroes(&self, sector: u64, count: u64, unmap: bool) -> Result<(), BlkError> {
+        use BlkError::*;
+        ensure!(self.support_write_zeroes(), NotSupported);
+        ensure!(!self.read_only(), ReadOnly);
+        self.check_range(sector, count)?;
+        let flags = (unmap && self.write_zeroes_may_unmap) as u32;
+        self.ranged(
+            BlkRequestType::WriteZeroes,
+            sector,
+            count,
+            self.max_write_zeroes_sectors,
+            flags,
+        )
+        .await
+    }
+
+    fn check_range(&self, sector: u64, count: u64) -> Result<(), BlkError> {
+        let end = sector.checked_add(count).ok_or(BlkError::OutOfRange)?;
+        ensure!(end <= self.capacity, BlkError::OutOfRange);
+        Ok(())
+    }
+
+    fn split(&self, segments: &[(PAddr, usize)]) -> Result<Vec<Vec<(PAddr, usize)>>, BlkError> {
+        use BlkError::*;
+        let mut pieces = Vec::new();
+        for &(paddr, size) in segments {
+            ensure!(size % SECTOR_SIZE == 0, BadBuffer);
+            let mut offset = 0;
+            while offset < size {
+                let len = usize::min(size - offset, self.size_max);
+                pieces.push((paddr + offset, len));
+                offset += len;
+            }
+        }
+        ensure!(!pieces.is_empty(), BadBuffer);
+        Ok(pieces.chunks(self.seg_max).map(<[_]>::to_vec).collect())
+    }
+
+    async fn request(
+        &self,
+        typa: BlkRequestType,
+        mut sector: u64,
+        segments: &[(PAddr, usize)],
+        keep: Option<Arc<dyn Send + Sync>>,
+    ) -> Result<(), BlkError> {
+        if let BlkRequestType::Out = typa {
+            ensure!(!self.read_only(), BlkError::ReadOnly);
+        }
+        let size = segments.iter().map(|&(_, size)| size).sum::<usize>();
+        self.check_range(sector, (size / SECTOR_SIZE) as u64)?;
+        for chunk in self.split(segments)? {
+            self.execute(typa, sector, &chunk, keep.clone()).await?;
+            let size = chunk.iter().map(|&(_, size)| size).sum::<usize>();
+            sector += (size / SECTOR_SIZE) as u64;
+        }
+        Ok(())
+    }
+
+    async fn ranged(
+        &self,
+        typa: BlkRequestType,
+        mut sector: u64,
+        mut count: u64,
+        limit: u32,
+        flags: u32,
+    ) -> Result<(), BlkError> {
+        while count != 0 {
+            let num_sectors = u64::min(count, limit as u64) as u32;
+            let range = Arc::new(Box::new_in(
+                BlkRange {
+                    sector,
+                    num_sectors,
+                    flags,
+                },
+                DmaAllocator,
+            ));
+            let RawDmaRef(paddr, size) = range.as_raw_dma_ref();
+            self.execute(typa, sector, &[(paddr, size)], Some(range))
+                .await?;
+            sector += num_sectors as u64;
+            count -= num_sectors as u64;
+        }
+        Ok(())
+    }
+
+    async fn execute(
+        &self,
+        typa: BlkRequestType,
+        sector: u64,
+        segments: &[(PAddr, usize)],
+        keep: Option<Arc<dyn Send + Sync>>,
+    ) -> Result<(), BlkError> {
+        let token = futures::future::poll_fn(|cx| {
+            let mut inner = self.inner.lock();
+            inner.collect();
+            match inner.submit(typa, sector, segments, keep.clone()) {
+                Some(token) => Poll::Ready(token),
+                None => {
+                    inner.waiters.push(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+        .await;
+        BlkWait {
+            blk: self,
+            token: Some(token),
+        }
+        .await
+    }
+}
+
+impl BlkInner {
+    fn submit(
+        &mut self,
+        typa: BlkRequestType,
+        sector: u64,
+        segments: &[(PAddr, usize)],
+        keep: Option<Arc<dyn Send + Sync>>,
+    ) -> Option<BlkToken> {
+        let request = Box::new_in(BlkRequestHeader::new(typa, sector), DmaAllocator);
         let status = Box::new_in(BlkStatus::Ok, DmaAllocator);
-        let idx = self
-            .queue
-            .push(
-                &[request.as_raw_dma_ref(), buffer.as_raw_dma_ref()],
-                &[status.as_raw_dma_mut()],
-            )
-            .unwrap();
-        let token = BlkToken(idx);
-        self.saves
-            .insert(token, BlkSave::Write(request, status, buffer));
+        let mut refs = vec![request.as_raw_dma_ref()];
+        let mut muts = Vec::new();
+        for &(paddr, size) in segments {
+            match typa {
+                BlkRequestType::In => muts.push(RawDmaMut(paddr, size)),
+                _ => refs.push(RawDmaRef(paddr, size)),
+            }
+        }
+        muts.push(status.as_raw_dma_mut());
+        self.mmio.queue_lock(QUEUE);
+        let pushed = self.queue.push(&refs, &muts);
         self.mmio.queue_unlock(QUEUE);
+        let token = BlkToken(pushed.ok()?);
+        self.saves.insert(
+            token,
+            BlkSave {
+                _request: request,
+                status,
+                _keep: keep,
+                state: BlkState::Pending(None),
+            },
+        );
         self.mmio.queue_notify(QUEUE);
-        Ok(token)
+        Some(token)
     }
 
-    pub fn poll(&mut self) -> Option<(BlkToken, BlkReturn)> {
+    fn collect(&mut self) {
+        let mut collected = false;
         self.mmio.queue_lock(QUEUE);
-        let token = self.queue.pop().map(|(id, _)| BlkToken(id))?;
-        let save = self.saves.remove(&token).unwrap();
-        let ret = match save {
-            BlkSave::Read(_, status, buffer) => BlkReturn::Read(*status, buffer),
-            BlkSave::Write(_, status, buffer) => BlkReturn::Write(*status, buffer),
-        };
+        while let Some((id, _)) = self.queue.pop() {
+            let token = BlkToken(id);
+            let save = self.saves.get_mut(&token).unwrap();
+            match core::mem::replace(&mut save.state, BlkState::Done) {
+                BlkState::Pending(waker) => waker.into_iter().for_each(Waker::wake),
+                BlkState::Done => unreachable!(),
+                BlkState::Abandoned => {
+                    self.saves.remove(&token);
+                }
+            }
+            collected = true;
+        }
         self.mmio.queue_unlock(QUEUE);
-        Some((token, ret))
+        if collected {
+            self.waiters.drain(..).for_each(Waker::wake);
+        }
+    }
+}
+
+partially!(BlkError, BlockError; NotSupported, BadBuffer, OutOfRange, ReadOnly, IOError);
+
+#[async_trait::async_trait]
+impl BlockDevice for Blk {
+    fn sector_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+    fn capacity(&self) -> u64 {
+        self.capacity
+    }
+    fn read_only(&self) -> bool {
+        self.features & F_RO != 0
+    }
+    async fn read(&self, sector: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        let dma = mem::dma::alloc_zeroed_slice(buffer.len());
+        let dma = self.read_sectors(sector, dma).await.out::<BlockError>()?;
+        buffer.copy_from_slice(&dma);
+        Ok(())
+    }
+    async fn write(&self, sector: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        let mut dma = mem::dma::alloc_zeroed_slice(buffer.len());
+        dma.copy_from_slice(buffer);
+        self.write_sectors(sector, dma).await.out::<BlockError>()?;
+        Ok(())
+    }
+    async fn flush(&self) -> Result<(), BlockError> {
+        Blk::flush(self).await.out::<BlockError>()
+    }
+}
+
+impl InterruptHandler for Blk {
+    fn interrupt(&self) {
+        let mut inner = self.inner.lock();
+        inner.mmio.interrupt_ack();
+        inner.collect();
+    }
+}
+
+struct BlkWait<'a> {
+    blk: &'a Blk,
+    token: Option<BlkToken>,
+}
+
+impl Future for BlkWait<'_> {
+    type Output = Result<(), BlkError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let token = self.token.unwrap();
+        let mut inner = self.blk.inner.lock();
+        inner.collect();
+        let save = inner.saves.get_mut(&token).unwrap();
+        if let BlkState::Pending(waker) = &mut save.state {
+            *waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let save = inner.saves.remove(&token).unwrap();
+        drop(inner);
+        self.token = None;
+        Poll::Ready(match *save.status {
+            BlkStatus::Ok => Ok(()),
+            BlkStatus::IOErr => Err(BlkError::IOError),
+            BlkStatus::Unsupported => Err(BlkError::NotSupported),
+        })
+    }
+}
+
+impl Drop for BlkWait<'_> {
+    fn drop(&mut self) {
+        if let Some(token) = self.token {
+            let mut inner = self.blk.inner.lock();
+            let save = inner.saves.get_mut(&token).unwrap();
+            if let BlkState::Done = save.state {
+                inner.saves.remove(&token);
+            } else {
+                save.state = BlkState::Abandoned;
+            }
+        }
     }
 }
 
 #[repr(u32)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum BlkRequestType {
     In = 0,
     Out = 1,
-    #[allow(dead_code)]
     Flush = 4,
-    #[allow(dead_code)]
     Discard = 11,
-    #[allow(dead_code)]
     WriteZeroes = 13,
 }
 
@@ -167,6 +552,14 @@ impl BlkRequestHeader {
     }
 }
 
+#[repr(C)]
+#[derive(Debug)]
+struct BlkRange {
+    sector: u64,
+    num_sectors: u32,
+    flags: u32,
+}
+
 #[repr(C)]
 #[derive(Debug)]
 struct BlkGeometry {
@@ -0,0 +1,190 @@
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use drivers::virtio::mmio::MMIO;
+use drivers::virtio::queue::VirtQueue;
+use drivers::virtio::DeviceType;
+use mem::dma::DmaAllocator;
+use spin::Mutex;
+
+type DmaBox<T> = Box<T, DmaAllocator>;
+
+pub const QUEUE: u32 = 0;
+
+const F_VERSION_1: u64 = 1 << 32;
+
+#[derive(Debug)]
+pub enum RngError {
+    NotARng,
+    NotSupported,
+    BadFeatures,
+    BadBuffer,
+}
+
+struct RngSave {
+    buffer: DmaBox<[u8]>,
+    state: RngState,
+}
+
+enum RngState {
+    Pending(Option<Waker>),
+    Done(usize),
+    Abandoned,
+}
+
+pub struct Rng {
+    inner: Mutex<RngInner>,
+}
+
+struct RngInner {
+    mmio: MMIO,
+    queue: VirtQueue,
+    saves: BTreeMap<u16, RngSave>,
+    waiters: Vec<Waker>,
+}
+
+impl Rng {
+    pub fn new(mut mmio: MMIO) -> Result<Self, RngError> {
+        if mmio.device() != DeviceType::EntropySource {
+            return Err(RngError::NotARng);
+        }
+        mmio.init_ack();
+        mmio.init_driver();
+        let features = mmio.features() & F_VERSION_1;
+        if !mmio.init_features_ok(features) {
+            mmio.init_failed();
+            return Err(RngError::BadFeatures);
+        }
+        mmio.queue_select(QUEUE);
+        let size = u32::min(config::VIRTIO_QUEUE_SIZE as u32, mmio.queue_max_size());
+        if size < 1 {
+            return Err(RngError::NotSupported);
+        }
+        let size = 1u16 << (31 - size.leading_zeros());
+        let queue = VirtQueue::new(&mut mmio, QUEUE, size).map_err(|_| RngError::NotSupported)?;
+        let rng = Rng {
+            inner: Mutex::new(RngInner {
+                mmio,
+                queue,
+                saves: BTreeMap::new(),
+                waiters: Vec::new(),
+            }),
+        };
+        rng.inner.lock().mmio.init_driver_ok();
+        Ok(rng)
+    }
+
+    pub async fn read(&self, buffer: &mut [u8]) -> Result<usize, RngError> {
+        ensure!(!buffer.is_empty(), RngError::BadBuffer);
+        let token = futures::future::poll_fn(|cx| {
+            let mut inner = self.inner.lock();
+            inner.collect();
+            match inner.submit(buffer.len()) {
+                Some(token) => Poll::Ready(token),
+                None => {
+                    inner.waiters.push(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+        .await;
+        let (data, len) = RngWait {
+            rng: self,
+            token: Some(token),
+        }
+        .await;
+        let len = usize::min(len, buffer.len());
+        buffer[..len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+}
+
+impl RngInner {
+    fn submit(&mut self, len: usize) -> Option<u16> {
+        let buffer = mem::dma::alloc_zeroed_slice(len);
+        self.mmio.queue_lock(QUEUE);
+        let pushed = self.queue.push(&[], &[buffer.as_raw_dma_mut()]);
+        self.mmio.queue_unlock(QUEUE);
+        let token = pushed.ok()?;
+        self.saves.insert(
+            token,
+            RngSave {
+                buffer,
+                state: RngState::Pending(None),
+            },
+        );
+        self.mmio.queue_notify(QUEUE);
+        Some(token)
+    }
+
+    fn collect(&mut self) {
+        let mut collected = false;
+        self.mmio.queue_lock(QUEUE);
+        while let Some((token, len)) = self.queue.pop() {
+            let save = self.saves.get_mut(&token).unwrap();
+            match core::mem::replace(&mut save.state, RngState::Done(len as usize)) {
+                RngState::Pending(waker) => waker.into_iter().for_each(Waker::wake),
+                RngState::Done(_) => unreachable!(),
+                RngState::Abandoned => {
+                    self.saves.remove(&token);
+                }
+            }
+            collected = true;
+        }
+        self.mmio.queue_unlock(QUEUE);
+        if collected {
+            self.waiters.drain(..).for_each(Waker::wake);
+        }
+    }
+}
+
+impl InterruptHandler for Rng {
+    fn interrupt(&self) {
+        let mut inner = self.inner.lock();
+        inner.mmio.interrupt_ack();
+        inner.collect();
+    }
+}
+
+struct RngWait<'a> {
+    rng: &'a Rng,
+    token: Option<u16>,
+}
+
+impl Future for RngWait<'_> {
+    type Output = (DmaBox<[u8]>, usize);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let token = self.token.unwrap();
+        let mut inner = self.rng.inner.lock();
+        inner.collect();
+        let save = inner.saves.get_mut(&token).unwrap();
+        if let RngState::Pending(waker) = &mut save.state {
+            *waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let save = inner.saves.remove(&token).unwrap();
+        drop(inner);
+        self.token = None;
+        match save.state {
+            RngState::Done(len) => Poll::Ready((save.buffer, len)),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Drop for RngWait<'_> {
+    fn drop(&mut self) {
+        if let Some(token) = self.token {
+            let mut inner = self.rng.inner.lock();
+            let save = inner.saves.get_mut(&token).unwrap();
+            if let RngState::Done(_) = save.state {
+                inner.saves.remove(&token);
+            } else {
+                save.state = RngState::Abandoned;
+            }
+        }
+    }
+}
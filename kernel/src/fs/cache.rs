@@ -0,0 +1,209 @@
+use crate::prelude::*;
+use alloc::collections::{BTreeMap, BTreeSet};
+use base::lock::AsyncMutex;
+use rt::paging::Paging;
+use spin::{Lazy, Mutex};
+use user::objects::memory::Memory;
+
+type PageTable = <P as Platform>::Paging;
+
+pub struct CachedFile {
+    inode: Arc<dyn Inode>,
+    io: AsyncMutex<()>,
+    pages: Mutex<BTreeMap<u64, Arc<Memory>>>,
+    dirty: Mutex<BTreeSet<u64>>,
+    mapped: Mutex<BTreeMap<u64, Vec<(Arc<PageTable>, VAddr)>>>,
+}
+
+impl CachedFile {
+    pub async fn page(&self, index: u64) -> Result<Arc<Memory>, FsError> {
+        if let Some(page) = self.pages.lock().get(&index) {
+            return Ok(page.clone());
+        }
+        let _guard = self.io.lock().await;
+        if let Some(page) = self.pages.lock().get(&index) {
+            return Ok(page.clone());
+        }
+        let m = config::PAGE_CACHE_LAYOUT.size();
+        let mut buffer = vec![0u8; m];
+        let offset = index * m as u64;
+        let mut ptr = 0;
+        while ptr < m {
+            let len = self
+                .inode
+                .read_at(offset + ptr as u64, &mut buffer[ptr..])
+                .await?;
+            if len == 0 {
+                break;
+            }
+            ptr += len;
+        }
+        let page = Memory::create(config::PAGE_CACHE_LAYOUT).map_err(|_| FsError::NoSpace)?;
+        page.write(0, &buffer);
+        self.pages.lock().insert(index, page.clone());
+        Ok(page)
+    }
+    pub fn cached(&self, index: u64) -> Option<Arc<Memory>> {
+        self.pages.lock().get(&index).cloned()
+    }
+    pub fn map_writable(&self, index: u64, page_table: &Arc<PageTable>, vaddr: VAddr) {
+        self.mapped
+            .lock()
+            .entry(index)
+            .or_default()
+            .push((page_table.clone(), vaddr));
+    }
+    pub fn unmap_writable(
+        &self,
+        index: u64,
+        page_table: &Arc<PageTable>,
+        vaddr: VAddr,
+        dirty: bool,
+    ) {
+        let mut mapped = self.mapped.lock();
+        let places = mapped.get_mut(&index).unwrap();
+        let position = places
+            .iter()
+            .position(|(x, y)| Arc::ptr_eq(x, page_table) && *y == vaddr)
+            .unwrap();
+        places.swap_remove(position);
+        if places.is_empty() {
+            mapped.remove(&index);
+        }
+        if dirty {
+            self.dirty.lock().insert(index);
+        }
+    }
+    pub fn update(&self, offset: u64, buffer: &[u8]) {
+        let m = config::PAGE_CACHE_LAYOUT.size() as u64;
+        let pages = self.pages.lock();
+        let end = offset + buffer.len() as u64;
+        for (&index, page) in pages.range(offset / m..end.div_ceil(m)) {
+            let start = u64::max(offset, index * m);
+            let stop = u64::min(end, (index + 1) * m);
+            page.write(
+                (start - index * m) as usize,
+                &buffer[(start - offset) as usize..(stop - offset) as usize],
+            );
+        }
+    }
+    pub async fn truncate(&self, size: u64) -> Result<(), FsError> {
+        let m = config::PAGE_CACHE_LAYOUT.size() as u64;
+        let _guard = self.io.lock().await;
+        self.inode.truncate(size).await?;
+        let zeros = vec![0u8; m as usize];
+        let mapped = self.mapped.lock();
+        self.pages.lock().retain(|&index, page| {
+            if (index + 1) * m <= size {
+                return true;
+            }
+            if index * m >= size && Arc::strong_count(page) == 1 && !mapped.contains_key(&index) {
+                return false;
+            }
+            let start = (u64::max(size, index * m) - index * m) as usize;
+            page.write(start, &zeros[start..]);
+            true
+        });
+        self.dirty.lock().retain(|&index| index * m < size);
+        Ok(())
+    }
+    fn clean(&self, dirty: &mut BTreeSet<u64>) {
+        let align = config::PAGE_CACHE_LAYOUT.align();
+        let mapped: Vec<_> = self
+            .mapped
+            .lock()
+            .iter()
+            .map(|(&index, places)| (index, places.clone()))
+            .collect();
+        for (index, places) in mapped {
+            let Some(page) = self.cached(index) else {
+                continue;
+            };
+            let paddr = page.index(0);
+            for (page_table, vaddr) in places {
+                if page_table.clean(vaddr, paddr, align) {
+                    dirty.insert(index);
+                }
+            }
+        }
+    }
+    pub async fn write_back(&self) -> Result<(), FsError> {
+        let m = config::PAGE_CACHE_LAYOUT.size();
+        let _guard = self.io.lock().await;
+        let mut dirty = core::mem::take(&mut *self.dirty.lock());
+        self.clean(&mut dirty);
+        let mut buffer = vec![0u8; m];
+        while let Some(index) = dirty.pop_first() {
+            let page = match self.pages.lock().get(&index) {
+                Some(page) => page.clone(),
+                None => continue,
+            };
+            let offset = index * m as u64;
+            let size = self.inode.metadata().size;
+            if offset >= size {
+                continue;
+            }
+            let len = usize::min(m, (size - offset) as usize);
+            page.read(0, &mut buffer[..len]);
+            if let Err(e) = self.inode.write_at(offset, &buffer[..len]).await {
+                dirty.insert(index);
+                self.dirty.lock().append(&mut dirty);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+    fn is_clean(&self) -> bool {
+        self.dirty.lock().is_empty() && self.mapped.lock().is_empty()
+    }
+}
+
+pub struct PageCache {
+    files: Mutex<BTreeMap<usize, Arc<CachedFile>>>,
+}
+
+fn id(inode: &Arc<dyn Inode>) -> usize {
+    Arc::as_ptr(inode) as *const () as usize
+}
+
+impl PageCache {
+    fn new() -> Self {
+        Self {
+            files: Mutex::new(BTreeMap::new()),
+        }
+    }
+    pub fn file(&self, inode: &Arc<dyn Inode>) -> Arc<CachedFile> {
+        let mut files = self.files.lock();
+        files.retain(|_, file| Arc::strong_count(file) > 1 || !file.is_clean());
+        files
+            .entry(id(inode))
+            .or_insert_with(|| {
+                Arc::new(CachedFile {
+                    inode: inode.clone(),
+                    io: AsyncMutex::new(()),
+                    pages: Mutex::new(BTreeMap::new()),
+                    dirty: Mutex::new(BTreeSet::new()),
+                    mapped: Mutex::new(BTreeMap::new()),
+                })
+            })
+            .clone()
+    }
+    pub fn lookup(&self, inode: &Arc<dyn Inode>) -> Option<Arc<CachedFile>> {
+        self.files.lock().get(&id(inode)).cloned()
+    }
+    pub async fn sync(&self) -> Result<(), FsError> {
+        let files: Vec<_> = self.files.lock().values().cloned().collect();
+        for file in files {
+            file.write_back().await?;
+        }
+        self.files
+            .lock()
+            .retain(|_, file| Arc::strong_count(file) > 1 || !file.is_clean());
+        Ok(())
+    }
+}
+
+pub fn cache() -> &'static PageCache {
+    static CACHE: Lazy<PageCache> = Lazy::new(PageCache::new);
+    &CACHE
+}
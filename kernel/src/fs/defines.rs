@@ -0,0 +1,99 @@
+use crate::prelude::*;
+use proc::vmm::MapUser;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    NotFound,
+    NotADirectory,
+    IsADirectory,
+    AlreadyExists,
+    NotEmpty,
+    BadPath,
+    ReadOnly,
+    PermissionDenied,
+    NoSpace,
+    Busy,
+    NotSupported,
+    BadFileSystem,
+    IOError,
+}
+
+impl From<drivers::block::BlockError> for FsError {
+    fn from(e: drivers::block::BlockError) -> Self {
+        use drivers::block::BlockError;
+        match e {
+            BlockError::ReadOnly => Self::ReadOnly,
+            BlockError::OutOfRange => Self::BadFileSystem,
+            BlockError::NotSupported | BlockError::BadBuffer | BlockError::IOError => Self::IOError,
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InodeType {
+    File = 0,
+    Directory = 1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    pub ino: u64,
+    pub typa: InodeType,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: String,
+    pub ino: u64,
+    pub typa: InodeType,
+}
+
+#[async_trait::async_trait]
+pub trait Inode: Send + Sync {
+    fn metadata(&self) -> Metadata;
+    async fn read_at(&self, _offset: u64, _buffer: &mut [u8]) -> Result<usize, FsError> {
+        Err(FsError::IsADirectory)
+    }
+    async fn write_at(&self, _offset: u64, _buffer: &[u8]) -> Result<usize, FsError> {
+        Err(FsError::ReadOnly)
+    }
+    async fn truncate(&self, _size: u64) -> Result<(), FsError> {
+        Err(FsError::ReadOnly)
+    }
+    fn mapping(&self, _offset: u64, _size: usize) -> Result<Arc<dyn MapUser>, FsError> {
+        Err(FsError::NotSupported)
+    }
+    async fn lookup(&self, _name: &str) -> Result<Arc<dyn Inode>, FsError> {
+        Err(FsError::NotADirectory)
+    }
+    async fn readdir(&self) -> Result<Vec<DirEntry>, FsError> {
+        Err(FsError::NotADirectory)
+    }
+    async fn create(&self, _name: &str, _typa: InodeType) -> Result<Arc<dyn Inode>, FsError> {
+        Err(FsError::ReadOnly)
+    }
+    async fn unlink(&self, _name: &str) -> Result<(), FsError> {
+        Err(FsError::ReadOnly)
+    }
+    async fn rename(
+        &self,
+        _name: &str,
+        _target: &Arc<dyn Inode>,
+        _new: &str,
+    ) -> Result<(), FsError> {
+        Err(FsError::ReadOnly)
+    }
+    async fn sync(&self) -> Result<(), FsError> {
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+pub trait FileSystem: Send + Sync {
+    fn root(&self) -> Arc<dyn Inode>;
+    async fn sync(&self) -> Result<(), FsError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,894 @@
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use base::lock::AsyncMutex;
+use core::sync::atomic::{AtomicU64, Ordering};
+use drivers::block::cache::cache;
+use drivers::block::BlockDevice;
+use fs::utils::*;
+use futures::future::{BoxFuture, FutureExt};
+use spin::Mutex;
+
+const SUPERBLOCK: u64 = 1024;
+const MAGIC: u16 = 0xef53;
+const ROOT_INO: u32 = 2;
+const RAW_SIZE: usize = 128;
+const DIRECT: u64 = 12;
+const S_IFMT: u16 = 0xf000;
+const S_IFREG: u16 = 0x8000;
+const S_IFDIR: u16 = 0x4000;
+const INCOMPAT_FILETYPE: u32 = 0x0002;
+const RO_COMPAT_SPARSE_SUPER: u32 = 0x0001;
+const RO_COMPAT_LARGE_FILE: u32 = 0x0002;
+const FT_REG_FILE: u8 = 1;
+const FT_DIR: u8 = 2;
+
+struct Ext2Group {
+    block_bitmap: u32,
+    inode_bitmap: u32,
+    inode_table: u32,
+    free_blocks: u16,
+    free_inodes: u16,
+    used_dirs: u16,
+}
+
+struct Ext2Alloc {
+    free_blocks: u32,
+    free_inodes: u32,
+}
+
+struct Ext2Inner {
+    device: Arc<dyn BlockDevice>,
+    read_only: bool,
+    block_size: u64,
+    blocks: u32,
+    first_data_block: u32,
+    blocks_per_group: u32,
+    inodes_per_group: u32,
+    inode_size: u64,
+    first_ino: u32,
+    filetype: bool,
+    large_file: bool,
+    gdt: u64,
+    groups: AsyncMutex<Vec<Ext2Group>>,
+    tables: Vec<u32>,
+    alloc: AsyncMutex<Ext2Alloc>,
+    inodes: Mutex<BTreeMap<u32, Weak<Ext2Inode>>>,
+}
+
+#[derive(Clone)]
+struct Raw([u8; RAW_SIZE]);
+
+impl Raw {
+    fn mode(&self) -> u16 {
+        u16_at(&self.0, 0)
+    }
+    fn typa(&self) -> InodeType {
+        match self.mode() & S_IFMT {
+            S_IFDIR => InodeType::Directory,
+            _ => InodeType::File,
+        }
+    }
+    fn size(&self) -> u64 {
+        let low = u32_at(&self.0, 4) as u64;
+        match self.mode() & S_IFMT {
+            S_IFREG => low | (u32_at(&self.0, 108) as u64) << 32,
+            _ => low,
+        }
+    }
+    fn set_size(&mut self, size: u64) {
+        set_u32_at(&mut self.0, 4, size as u32);
+        if self.mode() & S_IFMT == S_IFREG {
+            set_u32_at(&mut self.0, 108, (size >> 32) as u32);
+        }
+    }
+    fn links(&self) -> u16 {
+        u16_at(&self.0, 26)
+    }
+    fn set_links(&mut self, links: u16) {
+        set_u16_at(&mut self.0, 26, links);
+    }
+    fn sectors(&self) -> u32 {
+        u32_at(&self.0, 28)
+    }
+    fn set_sectors(&mut self, sectors: u32) {
+        set_u32_at(&mut self.0, 28, sectors);
+    }
+    fn block(&self, index: usize) -> u32 {
+        u32_at(&self.0, 40 + 4 * index)
+    }
+    fn set_block(&mut self, index: usize, block: u32) {
+        set_u32_at(&mut self.0, 40 + 4 * index, block);
+    }
+}
+
+impl Ext2Inner {
+    async fn read(&self, offset: u64, buffer: &mut [u8]) -> Result<(), FsError> {
+        Ok(cache().read(&self.device, offset, buffer).await?)
+    }
+    async fn write(&self, offset: u64, buffer: &[u8]) -> Result<(), FsError> {
+        ensure!(!self.read_only, FsError::ReadOnly);
+        Ok(cache().write(&self.device, offset, buffer).await?)
+    }
+    fn block(&self, block: u32) -> u64 {
+        block as u64 * self.block_size
+    }
+    fn per_block(&self) -> u64 {
+        self.block_size / 4
+    }
+    fn group_of(&self, ino: u32) -> usize {
+        ((ino - 1) / self.inodes_per_group) as usize
+    }
+    fn locate(&self, ino: u32) -> Result<u64, FsError> {
+        ensure!(ino != 0, FsError::BadFileSystem);
+        let group = self.group_of(ino);
+        ensure!(group < self.tables.len(), FsError::BadFileSystem);
+        let index = ((ino - 1) % self.inodes_per_group) as u64;
+        Ok(self.block(self.tables[group]) + index * self.inode_size)
+    }
+    async fn read_raw(&self, ino: u32) -> Result<Raw, FsError> {
+        let mut raw = Raw([0u8; RAW_SIZE]);
+        self.read(self.locate(ino)?, &mut raw.0).await?;
+        Ok(raw)
+    }
+    async fn write_raw(&self, ino: u32, raw: &Raw) -> Result<(), FsError> {
+        self.write(self.locate(ino)?, &raw.0).await
+    }
+    async fn ptr(&self, block: u32, index: u64) -> Result<u32, FsError> {
+        let mut buffer = [0u8; 4];
+        self.read(self.block(block) + 4 * index, &mut buffer)
+            .await?;
+        Ok(u32::from_le_bytes(buffer))
+    }
+    async fn set_ptr(&self, block: u32, index: u64, value: u32) -> Result<(), FsError> {
+        self.write(self.block(block) + 4 * index, &value.to_le_bytes())
+            .await
+    }
+    async fn write_group(&self, index: usize, group: &Ext2Group) -> Result<(), FsError> {
+        let mut buffer = [0u8; 6];
+        set_u16_at(&mut buffer, 0, group.free_blocks);
+        set_u16_at(&mut buffer, 2, group.free_inodes);
+        set_u16_at(&mut buffer, 4, group.used_dirs);
+        self.write(self.gdt + 32 * index as u64 + 12, &buffer).await
+    }
+    async fn write_counts(&self, alloc: &Ext2Alloc) -> Result<(), FsError> {
+        let mut buffer = [0u8; 8];
+        set_u32_at(&mut buffer, 0, alloc.free_blocks);
+        set_u32_at(&mut buffer, 4, alloc.free_inodes);
+        self.write(SUPERBLOCK + 12, &buffer).await
+    }
+    async fn bitmap_take(&self, bitmap: u32, limit: u32) -> Result<Option<u32>, FsError> {
+        let mut buffer = vec![0u8; self.block_size as usize];
+        self.read(self.block(bitmap), &mut buffer).await?;
+        let bit = match (0..limit).find(|&i| buffer[(i / 8) as usize] & 1 << (i % 8) == 0) {
+            Some(bit) => bit,
+            None => return Ok(None),
+        };
+        let byte = buffer[(bit / 8) as usize] | 1 << (bit % 8);
+        self.write(self.block(bitmap) + (bit / 8) as u64, &[byte])
+            .await?;
+        Ok(Some(bit))
+    }
+    async fn bitmap_clear(&self, bitmap: u32, bit: u32) -> Result<(), FsError> {
+        let offset = self.block(bitmap) + (bit / 8) as u64;
+        let mut byte = [0u8; 1];
+        self.read(offset, &mut byte).await?;
+        ensure!(byte[0] & 1 << (bit % 8) != 0, FsError::BadFileSystem);
+        byte[0] &= !(1 << (bit % 8));
+        self.write(offset, &byte).await
+    }
+    async fn alloc_block(&self, goal: usize) -> Result<u32, FsError> {
+        ensure!(!self.read_only, FsError::ReadOnly);
+        let mut groups = self.groups.lock().await;
+        let count = groups.len();
+        for k in 0..count {
+            let index = (goal + k) % count;
+            if groups[index].free_blocks == 0 {
+                continue;
+            }
+            let start = self.first_data_block + index as u32 * self.blocks_per_group;
+            let limit = u32::min(self.blocks_per_group, self.blocks - start);
+            if let Some(bit) = self.bitmap_take(groups[index].block_bitmap, limit).await? {
+                groups[index].free_blocks -= 1;
+                self.write_group(index, &groups[index]).await?;
+                let mut alloc = self.alloc.lock().await;
+                alloc.free_blocks = alloc.free_blocks.saturating_sub(1);
+                self.write_counts(&alloc).await?;
+                drop(alloc);
+                drop(groups);
+                let block = start + bit;
+                let zeroes = vec![0u8; self.block_size as usize];
+                self.write(self.block(block), &zeroes).await?;
+                return Ok(block);
+            }
+        }
+        Err(FsError::NoSpace)
+    }
+    async fn free_block(&self, block: u32) -> Result<(), FsError> {
+        ensure!(
+            self.first_data_block <= block && block < self.blocks,
+            FsError::BadFileSystem
+        );
+        let mut groups = self.groups.lock().await;
+        let index = ((block - self.first_data_block) / self.blocks_per_group) as usize;
+        let bit = (block - self.first_data_block) % self.blocks_per_group;
+        self.bitmap_clear(groups[index].block_bitmap, bit).await?;
+        groups[index].free_blocks += 1;
+        self.write_group(index, &groups[index]).await?;
+        let mut alloc = self.alloc.lock().await;
+        alloc.free_blocks += 1;
+        self.write_counts(&alloc).await
+    }
+    async fn alloc_inode(&self, goal: usize, directory: bool) -> Result<u32, FsError> {
+        ensure!(!self.read_only, FsError::ReadOnly);
+        let mut groups = self.groups.lock().await;
+        let count = groups.len();
+        for k in 0..count {
+            let index = (goal + k) % count;
+            if groups[index].free_inodes == 0 {
+                continue;
+            }
+            let bitmap = groups[index].inode_bitmap;
+            if let Some(bit) = self.bitmap_take(bitmap, self.inodes_per_group).await? {
+                let ino = index as u32 * self.inodes_per_group + bit + 1;
+                ensure!(ino >= self.first_ino, FsError::BadFileSystem);
+                groups[index].free_inodes -= 1;
+                if directory {
+                    groups[index].used_dirs += 1;
+                }
+                self.write_group(index, &groups[index]).await?;
+                let mut alloc = self.alloc.lock().await;
+                alloc.free_inodes = alloc.free_inodes.saturating_sub(1);
+                self.write_counts(&alloc).await?;
+                return Ok(ino);
+            }
+        }
+        Err(FsError::NoSpace)
+    }
+    async fn free_inode(&self, ino: u32, directory: bool) -> Result<(), FsError> {
+        let mut groups = self.groups.lock().await;
+        let index = self.group_of(ino);
+        let bit = (ino - 1) % self.inodes_per_group;
+        self.bitmap_clear(groups[index].inode_bitmap, bit).await?;
+        groups[index].free_inodes += 1;
+        if directory {
+            groups[index].used_dirs = groups[index].used_dirs.saturating_sub(1);
+        }
+        self.write_group(index, &groups[index]).await?;
+        let mut alloc = self.alloc.lock().await;
+        alloc.free_inodes += 1;
+        self.write_counts(&alloc).await
+    }
+    fn prune(
+        &self,
+        block: u32,
+        depth: u32,
+        base: u64,
+        keep: u64,
+    ) -> BoxFuture<'_, Result<(bool, u32), FsError>> {
+        async move {
+            let span = self.per_block().pow(depth - 1);
+            let mut ptrs = vec![0u8; self.block_size as usize];
+            self.read(self.block(block), &mut ptrs).await?;
+            let mut freed = 0;
+            let mut dirty = false;
+            for j in 0..self.per_block() {
+                let child_base = base + j * span;
+                if child_base + span <= keep {
+                    continue;
+                }
+                let child = u32_at(&ptrs, 4 * j as usize);
+                if child == 0 {
+                    continue;
+                }
+                if depth > 1 {
+                    let (gone, count) = self.prune(child, depth - 1, child_base, keep).await?;
+                    freed += count;
+                    if !gone {
+                        continue;
+                    }
+                } else {
+                    self.free_block(child).await?;
+                    freed += 1;
+                }
+                set_u32_at(&mut ptrs, 4 * j as usize, 0);
+                dirty = true;
+            }
+            if keep <= base {
+                self.free_block(block).await?;
+                return Ok((true, freed + 1));
+            }
+            if dirty {
+                self.write(self.block(block), &ptrs).await?;
+            }
+            Ok((false, freed))
+        }
+        .boxed()
+    }
+}
+
+struct Ext2Record {
+    ino: u32,
+    name: String,
+    kind: u8,
+    offset: u64,
+    len: u64,
+    prev: Option<u64>,
+}
+
+impl Ext2Record {
+    fn used(&self) -> u64 {
+        match self.ino {
+            0 => 0,
+            _ => (8 + self.name.len() as u64).next_multiple_of(4),
+        }
+    }
+    fn dot(&self) -> bool {
+        self.name == "." || self.name == ".."
+    }
+}
+
+pub struct Ext2Inode {
+    fs: Arc<Ext2Inner>,
+    ino: u32,
+    typa: InodeType,
+    size: AtomicU64,
+    lock: AsyncMutex<()>,
+}
+
+impl Ext2Inode {
+    async fn get(fs: &Arc<Ext2Inner>, ino: u32) -> Result<Arc<Ext2Inode>, FsError> {
+        if let Some(inode) = fs.inodes.lock().get(&ino).and_then(Weak::upgrade) {
+            return Ok(inode);
+        }
+        let raw = fs.read_raw(ino).await?;
+        ensure!(raw.links() != 0, FsError::BadFileSystem);
+        let mut inodes = fs.inodes.lock();
+        if let Some(inode) = inodes.get(&ino).and_then(Weak::upgrade) {
+            return Ok(inode);
+        }
+        inodes.retain(|_, inode| inode.strong_count() != 0);
+        let inode = Arc::new(Ext2Inode {
+            fs: fs.clone(),
+            ino,
+            typa: raw.typa(),
+            size: AtomicU64::new(raw.size()),
+            lock: AsyncMutex::new(()),
+        });
+        inodes.insert(ino, Arc::downgrade(&inode));
+        Ok(inode)
+    }
+    fn goal(&self) -> usize {
+        self.fs.group_of(self.ino)
+    }
+    async fn bmap(&self, raw: &mut Raw, index: u64, create: bool) -> Result<u32, FsError> {
+        let fs = &self.fs;
+        let sectors = (fs.block_size / 512) as u32;
+        if index < DIRECT {
+            let mut block = raw.block(index as usize);
+            if block == 0 && create {
+                block = fs.alloc_block(self.goal()).await?;
+                raw.set_block(index as usize, block);
+                raw.set_sectors(raw.sectors() + sectors);
+            }
+            return Ok(block);
+        }
+        let mut index = index - DIRECT;
+        let mut depth = 1;
+        while index >= fs.per_block().pow(depth) {
+            index -= fs.per_block().pow(depth);
+            depth += 1;
+            ensure!(depth <= 3, FsError::NoSpace);
+        }
+        let slot = DIRECT as usize + depth as usize - 1;
+        let mut block = raw.block(slot);
+        if block == 0 {
+            if !create {
+                return Ok(0);
+            }
+            block = fs.alloc_block(self.goal()).await?;
+            raw.set_block(slot, block);
+            raw.set_sectors(raw.sectors() + sectors);
+        }
+        for level in (0..depth).rev() {
+            let span = fs.per_block().pow(level);
+            let i = index / span;
+            index %= span;
+            let mut next = fs.ptr(block, i).await?;
+            if next == 0 {
+                if !create {
+                    return Ok(0);
+                }
+                next = fs.alloc_block(self.goal()).await?;
+                fs.set_ptr(block, i, next).await?;
+                raw.set_sectors(raw.sectors() + sectors);
+            }
+            block = next;
+        }
+        Ok(block)
+    }
+    async fn read_data(
+        &self,
+        raw: &mut Raw,
+        mut offset: u64,
+        buffer: &mut [u8],
+    ) -> Result<(), FsError> {
+        let bs = self.fs.block_size;
+        let mut done = 0;
+        while done < buffer.len() {
+            let inside = offset % bs;
+            let len = usize::min(buffer.len() - done, (bs - inside) as usize);
+            match self.bmap(raw, offset / bs, false).await? {
+                0 => buffer[done..done + len].fill(0),
+                block => {
+                    self.fs
+                        .read(self.fs.block(block) + inside, &mut buffer[done..done + len])
+                        .await?
+                }
+            }
+            done += len;
+            offset += len as u64;
+        }
+        Ok(())
+    }
+    async fn write_data(
+        &self,
+        raw: &mut Raw,
+        mut offset: u64,
+        buffer: &[u8],
+    ) -> Result<(), FsError> {
+        let bs = self.fs.block_size;
+        let mut done = 0;
+        while done < buffer.len() {
+            let inside = offset % bs;
+            let len = usize::min(buffer.len() - done, (bs - inside) as usize);
+            let block = self.bmap(raw, offset / bs, true).await?;
+            self.fs
+                .write(self.fs.block(block) + inside, &buffer[done..done + len])
+                .await?;
+            done += len;
+            offset += len as u64;
+        }
+        Ok(())
+    }
+    async fn prune(&self, raw: &mut Raw, size: u64) -> Result<(), FsError> {
+        let fs = &self.fs;
+        let bs = fs.block_size;
+        if size % bs != 0 {
+            let block = self.bmap(raw, size / bs, false).await?;
+            if block != 0 {
+                let zeroes = vec![0u8; (bs - size % bs) as usize];
+                fs.write(fs.block(block) + size % bs, &zeroes).await?;
+            }
+        }
+        let keep = size.div_ceil(bs);
+        let mut freed = 0;
+        for index in keep..DIRECT {
+            let block = raw.block(index as usize);
+            if block != 0 {
+                fs.free_block(block).await?;
+                raw.set_block(index as usize, 0);
+                freed += 1;
+            }
+        }
+        let mut base = DIRECT;
+        for depth in 1..=3 {
+            let slot = DIRECT as usize + depth as usize - 1;
+            let block = raw.block(slot);
+            if block != 0 {
+                let (gone, count) = fs.prune(block, depth, base, keep).await?;
+                freed += count;
+                if gone {
+                    raw.set_block(slot, 0);
+                }
+            }
+            base += fs.per_block().pow(depth);
+        }
+        let sectors = (bs / 512) as u32;
+        raw.set_sectors(raw.sectors().saturating_sub(freed * sectors));
+        Ok(())
+    }
+    async fn records(&self, raw: &mut Raw) -> Result<Vec<Ext2Record>, FsError> {
+        let bs = self.fs.block_size;
+        let size = raw.size();
+        ensure!(size % bs == 0, FsError::BadFileSystem);
+        let mut data = vec![0u8; size as usize];
+        self.read_data(raw, 0, &mut data).await?;
+        let mut records = Vec::new();
+        for (index, block) in data.chunks_exact(bs as usize).enumerate() {
+            let mut pos = 0;
+            let mut prev = None;
+            while pos < bs as usize {
+                ensure!(pos + 8 <= bs as usize, FsError::BadFileSystem);
+                let len = u16_at(block, pos + 4) as usize;
+                let name_len = block[pos + 6] as usize;
+                ensure!(
+                    len >= 8 + name_len && len % 4 == 0 && pos + len <= bs as usize,
+                    FsError::BadFileSystem
+                );
+                let offset = index as u64 * bs + pos as u64;
+                let name = &block[pos + 8..pos + 8 + name_len];
+                records.push(Ext2Record {
+                    ino: u32_at(block, pos),
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    kind: if self.fs.filetype { block[pos + 7] } else { 0 },
+                    offset,
+                    len: len as u64,
+                    prev,
+                });
+                prev = Some(offset);
+                pos += len;
+            }
+        }
+        Ok(records)
+    }
+    fn find<'a>(records: &'a [Ext2Record], name: &str) -> Option<&'a Ext2Record> {
+        records
+            .iter()
+            .find(|record| record.ino != 0 && !record.dot() && record.name == name)
+    }
+    fn encode(&self, ino: u32, len: u64, name: &str, typa: InodeType) -> Vec<u8> {
+        let mut buffer = vec![0u8; 8 + name.len()];
+        set_u32_at(&mut buffer, 0, ino);
+        set_u16_at(&mut buffer, 4, len as u16);
+        buffer[6] = name.len() as u8;
+        if self.fs.filetype {
+            buffer[7] = match typa {
+                InodeType::File => FT_REG_FILE,
+                InodeType::Directory => FT_DIR,
+            };
+        }
+        buffer[8..].copy_from_slice(name.as_bytes());
+        buffer
+    }
+    async fn insert(
+        &self,
+        raw: &mut Raw,
+        records: &[Ext2Record],
+        name: &str,
+        ino: u32,
+        typa: InodeType,
+    ) -> Result<(), FsError> {
+        let needed = (8 + name.len() as u64).next_multiple_of(4);
+        for record in records.iter() {
+            let used = record.used();
+            if record.len - used < needed {
+                continue;
+            }
+            if used != 0 {
+                let mut len = [0u8; 2];
+                set_u16_at(&mut len, 0, used as u16);
+                self.write_data(raw, record.offset + 4, &len).await?;
+            }
+            let entry = self.encode(ino, record.len - used, name, typa);
+            return self.write_data(raw, record.offset + used, &entry).await;
+        }
+        let size = raw.size();
+        let entry = self.encode(ino, self.fs.block_size, name, typa);
+        self.write_data(raw, size, &entry).await?;
+        raw.set_size(size + self.fs.block_size);
+        self.size.store(raw.size(), Ordering::Relaxed);
+        Ok(())
+    }
+    async fn remove(&self, raw: &mut Raw, record: &Ext2Record) -> Result<(), FsError> {
+        match record.prev {
+            Some(prev) => {
+                let mut len = [0u8; 2];
+                self.read_data(raw, prev + 4, &mut len).await?;
+                let len = (u16_at(&len, 0) as u64 + record.len) as u16;
+                self.write_data(raw, prev + 4, &len.to_le_bytes()).await
+            }
+            None => self.write_data(raw, record.offset, &[0u8; 4]).await,
+        }
+    }
+}
+
+fn check(name: &str) -> Result<(), FsError> {
+    ensure!(
+        !name.is_empty() && name != "." && name != "..",
+        FsError::BadPath
+    );
+    ensure!(name.len() <= 255 && !name.contains('/'), FsError::BadPath);
+    ensure!(!name.contains('\0'), FsError::BadPath);
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl Inode for Ext2Inode {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            ino: self.ino as u64,
+            typa: self.typa,
+            size: self.size.load(Ordering::Relaxed),
+        }
+    }
+    async fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        ensure!(self.typa == InodeType::File, FsError::IsADirectory);
+        let _guard = self.lock.lock().await;
+        let mut raw = self.fs.read_raw(self.ino).await?;
+        ensure!(raw.mode() & S_IFMT == S_IFREG, FsError::NotSupported);
+        let size = raw.size();
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = u64::min(buffer.len() as u64, size - offset) as usize;
+        self.read_data(&mut raw, offset, &mut buffer[..len]).await?;
+        Ok(len)
+    }
+    async fn write_at(&self, offset: u64, buffer: &[u8]) -> Result<usize, FsError> {
+        ensure!(self.typa == InodeType::File, FsError::IsADirectory);
+        ensure!(!self.fs.read_only, FsError::ReadOnly);
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+        let _guard = self.lock.lock().await;
+        let mut raw = self.fs.read_raw(self.ino).await?;
+        ensure!(raw.mode() & S_IFMT == S_IFREG, FsError::NotSupported);
+        let limit = if self.fs.large_file {
+            i64::MAX as u64
+        } else {
+            i32::MAX as u64
+        };
+        let end = offset
+            .checked_add(buffer.len() as u64)
+            .filter(|&end| end <= limit)
+            .ok_or(FsError::NoSpace)?;
+        let result = self.write_data(&mut raw, offset, buffer).await;
+        if result.is_ok() && end > raw.size() {
+            raw.set_size(end);
+            self.size.store(end, Ordering::Relaxed);
+        }
+        self.fs.write_raw(self.ino, &raw).await?;
+        result.map(|_| buffer.len())
+    }
+    async fn truncate(&self, size: u64) -> Result<(), FsError> {
+        ensure!(self.typa == InodeType::File, FsError::IsADirectory);
+        ensure!(!self.fs.read_only, FsError::ReadOnly);
+        let _guard = self.lock.lock().await;
+        let mut raw = self.fs.read_raw(self.ino).await?;
+        ensure!(raw.mode() & S_IFMT == S_IFREG, FsError::NotSupported);
+        let limit = if self.fs.large_file {
+            i64::MAX as u64
+        } else {
+            i32::MAX as u64
+        };
+        ensure!(size <= limit, FsError::NoSpace);
+        if size < raw.size() {
+            self.prune(&mut raw, size).await?;
+        }
+        raw.set_size(size);
+        self.size.store(size, Ordering::Relaxed);
+        self.fs.write_raw(self.ino, &raw).await
+    }
+    async fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FsError> {
+        ensure!(self.typa == InodeType::Directory, FsError::NotADirectory);
+        let _guard = self.lock.lock().await;
+        let mut raw = self.fs.read_raw(self.ino).await?;
+        let records = self.records(&mut raw).await?;
+        let record = Self::find(&records, name).ok_or(FsError::NotFound)?;
+        Ok(Ext2Inode::get(&self.fs, record.ino).await?)
+    }
+    async fn readdir(&self) -> Result<Vec<DirEntry>, FsError> {
+        ensure!(self.typa == InodeType::Directory, FsError::NotADirectory);
+        let _guard = self.lock.lock().await;
+        let mut raw = self.fs.read_raw(self.ino).await?;
+        let records = self.records(&mut raw).await?;
+        let mut entries = Vec::new();
+        for record in records
+            .iter()
+            .filter(|record| record.ino != 0 && !record.dot())
+        {
+            let typa = match record.kind {
+                FT_DIR => InodeType::Directory,
+                FT_REG_FILE => InodeType::File,
+                _ => self.fs.read_raw(record.ino).await?.typa(),
+            };
+            entries.push(DirEntry {
+                name: record.name.clone(),
+                ino: record.ino as u64,
+                typa,
+            });
+        }
+        Ok(entries)
+    }
+    async fn create(&self, name: &str, typa: InodeType) -> Result<Arc<dyn Inode>, FsError> {
+        ensure!(self.typa == InodeType::Directory, FsError::NotADirectory);
+        ensure!(!self.fs.read_only, FsError::ReadOnly);
+        check(name)?;
+        let _guard = self.lock.lock().await;
+        let mut raw = self.fs.read_raw(self.ino).await?;
+        let records = self.records(&mut raw).await?;
+        ensure!(Self::find(&records, name).is_none(), FsError::AlreadyExists);
+        let directory = typa == InodeType::Directory;
+        let ino = self.fs.alloc_inode(self.goal(), directory).await?;
+        let zeroes = vec![0u8; self.fs.inode_size as usize];
+        self.fs.write(self.fs.locate(ino)?, &zeroes).await?;
+        let mut child = Raw([0u8; RAW_SIZE]);
+        match typa {
+            InodeType::File => {
+                set_u16_at(&mut child.0, 0, S_IFREG | 0o644);
+                child.set_links(1);
+            }
+            InodeType::Directory => {
+                set_u16_at(&mut child.0, 0, S_IFDIR | 0o755);
+                child.set_links(2);
+                let bs = self.fs.block_size;
+                let block = self.fs.alloc_block(self.fs.group_of(ino)).await?;
+                let mut dots = self.encode(ino, 12, ".", InodeType::Directory);
+                dots.resize(12, 0);
+                dots.extend(self.encode(self.ino, bs - 12, "..", InodeType::Directory));
+                self.fs.write(self.fs.block(block), &dots).await?;
+                child.set_block(0, block);
+                child.set_sectors((bs / 512) as u32);
+                child.set_size(bs);
+                raw.set_links(raw.links() + 1);
+            }
+        }
+        self.fs.write_raw(ino, &child).await?;
+        self.insert(&mut raw, &records, name, ino, typa).await?;
+        self.fs.write_raw(self.ino, &raw).await?;
+        Ok(Ext2Inode::get(&self.fs, ino).await?)
+    }
+    async fn unlink(&self, name: &str) -> Result<(), FsError> {
+        ensure!(self.typa == InodeType::Directory, FsError::NotADirectory);
+        ensure!(!self.fs.read_only, FsError::ReadOnly);
+        let _guard = self.lock.lock().await;
+        let mut raw = self.fs.read_raw(self.ino).await?;
+        let records = self.records(&mut raw).await?;
+        let record = Self::find(&records, name).ok_or(FsError::NotFound)?;
+        let inode = Ext2Inode::get(&self.fs, record.ino).await?;
+        ensure!(Arc::strong_count(&inode) == 1, FsError::Busy);
+        let mut child = self.fs.read_raw(record.ino).await?;
+        let directory = child.typa() == InodeType::Directory;
+        if directory {
+            let children = inode.records(&mut child).await?;
+            ensure!(
+                children
+                    .iter()
+                    .all(|record| record.ino == 0 || record.dot()),
+                FsError::NotEmpty
+            );
+        }
+        self.remove(&mut raw, record).await?;
+        if directory {
+            child.set_links(0);
+            raw.set_links(raw.links().saturating_sub(1));
+        } else {
+            child.set_links(child.links().saturating_sub(1));
+        }
+        self.fs.write_raw(self.ino, &raw).await?;
+        self.fs.inodes.lock().remove(&record.ino);
+        if child.links() == 0 {
+            inode.prune(&mut child, 0).await?;
+            child.set_size(0);
+            set_u32_at(&mut child.0, 20, 1);
+            self.fs.write_raw(record.ino, &child).await?;
+            self.fs.free_inode(record.ino, directory).await?;
+        } else {
+            self.fs.write_raw(record.ino, &child).await?;
+        }
+        Ok(())
+    }
+    async fn sync(&self) -> Result<(), FsError> {
+        Ok(cache().sync(Some(&self.fs.device)).await?)
+    }
+}
+
+pub struct Ext2 {
+    inner: Arc<Ext2Inner>,
+    root: Arc<Ext2Inode>,
+}
+
+impl Ext2 {
+    pub async fn mount(device: Arc<dyn BlockDevice>) -> Result<Arc<Ext2>, FsError> {
+        use FsError::*;
+        let mut sb = [0u8; 1024];
+        cache()
+            .read(&device, SUPERBLOCK, &mut sb)
+            .await
+            .map_err(|_| BadFileSystem)?;
+        ensure!(u16_at(&sb, 56) == MAGIC, BadFileSystem);
+        let inodes = u32_at(&sb, 0);
+        let blocks = u32_at(&sb, 4);
+        let first_data_block = u32_at(&sb, 20);
+        let log_block_size = u32_at(&sb, 24);
+        let blocks_per_group = u32_at(&sb, 32);
+        let inodes_per_group = u32_at(&sb, 40);
+        let revision = u32_at(&sb, 76);
+        let (first_ino, inode_size, incompat, ro_compat) = match revision {
+            0 => (11, 128, 0, 0),
+            _ => (
+                u32_at(&sb, 84),
+                u16_at(&sb, 88) as u64,
+                u32_at(&sb, 96),
+                u32_at(&sb, 100),
+            ),
+        };
+        ensure!(incompat & !INCOMPAT_FILETYPE == 0, NotSupported);
+        ensure!(log_block_size <= 5, BadFileSystem);
+        let block_size = 1024u64 << log_block_size;
+        ensure!(
+            blocks_per_group != 0 && inodes_per_group != 0 && blocks > first_data_block,
+            BadFileSystem
+        );
+        ensure!(
+            blocks_per_group as u64 <= 8 * block_size && inodes_per_group as u64 <= 8 * block_size,
+            BadFileSystem
+        );
+        ensure!(
+            inode_size >= RAW_SIZE as u64
+                && inode_size <= block_size
+                && inode_size.is_power_of_two(),
+            BadFileSystem
+        );
+        ensure!(
+            blocks as u64 * block_size <= device.capacity() * device.sector_size() as u64,
+            BadFileSystem
+        );
+        let count = (blocks - first_data_block).div_ceil(blocks_per_group) as usize;
+        ensure!(
+            count as u64 * inodes_per_group as u64 >= inodes as u64,
+            BadFileSystem
+        );
+        let gdt = (first_data_block as u64 + 1) * block_size;
+        let mut buffer = vec![0u8; 32 * count];
+        cache()
+            .read(&device, gdt, &mut buffer)
+            .await
+            .map_err(|_| BadFileSystem)?;
+        let groups = buffer
+            .chunks_exact(32)
+            .map(|desc| Ext2Group {
+                block_bitmap: u32_at(desc, 0),
+                inode_bitmap: u32_at(desc, 4),
+                inode_table: u32_at(desc, 8),
+                free_blocks: u16_at(desc, 12),
+                free_inodes: u16_at(desc, 14),
+                used_dirs: u16_at(desc, 16),
+            })
+            .collect::<Vec<_>>();
+        ensure!(
+            groups.iter().all(
+                |group| [group.block_bitmap, group.inode_bitmap, group.inode_table]
+                    .iter()
+                    .all(|&block| first_data_block <= block && block < blocks)
+            ),
+            BadFileSystem
+        );
+        let known = RO_COMPAT_SPARSE_SUPER | RO_COMPAT_LARGE_FILE;
+        let read_only = device.read_only() || ro_compat & !known != 0;
+        let inner = Arc::new(Ext2Inner {
+            device,
+            read_only,
+            block_size,
+            blocks,
+            first_data_block,
+            blocks_per_group,
+            inodes_per_group,
+            inode_size,
+            first_ino,
+            filetype: incompat & INCOMPAT_FILETYPE != 0,
+            large_file: ro_compat & RO_COMPAT_LARGE_FILE != 0,
+            gdt,
+            tables: groups.iter().map(|group| group.inode_table).collect(),
+            groups: AsyncMutex::new(groups),
+            alloc: AsyncMutex::new(Ext2Alloc {
+                free_blocks: u32_at(&sb, 12),
+                free_inodes: u32_at(&sb, 16),
+            }),
+            inodes: Mutex::new(BTreeMap::new()),
+        });
+        let root = Ext2Inode::get(&inner, ROOT_INO).await?;
+        ensure!(root.typa == InodeType::Directory, BadFileSystem);
+        Ok(Arc::new(Ext2 { inner, root }))
+    }
+}
+
+#[async_trait::async_trait]
+impl FileSystem for Ext2 {
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+    async fn sync(&self) -> Result<(), FsError> {
+        Ok(cache().sync(Some(&self.inner.device)).await?)
+    }
+}
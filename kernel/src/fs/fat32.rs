@@ -0,0 +1,801 @@
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use base::lock::AsyncMutex;
+use core::sync::atomic::{AtomicU32, Ordering};
+use drivers::block::cache::cache;
+use drivers::block::BlockDevice;
+use fs::utils::*;
+use spin::Mutex;
+
+const ENTRY_SIZE: usize = 32;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LONG_NAME: u8 = 0x0f;
+const FAT_MASK: u32 = 0x0fffffff;
+const FAT_EOC: u32 = 0x0fffffff;
+const FAT_LAST: u32 = 0x0ffffff8;
+const DATE: u16 = 0x0021;
+const ROOT_INO: u64 = 1;
+const MAX_DIR_SIZE: usize = 65536 * ENTRY_SIZE;
+const LONG_OFFSETS: [usize; 13] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+fn checksum(short: &[u8]) -> u8 {
+    short.iter().fold(0u8, |sum, &c| {
+        (sum >> 1).wrapping_add(sum << 7).wrapping_add(c)
+    })
+}
+
+fn short_char(c: char) -> Option<u8> {
+    match c {
+        'A'..='Z' | '0'..='9' => Some(c as u8),
+        'a'..='z' => Some(c.to_ascii_uppercase() as u8),
+        '$' | '%' | '\'' | '-' | '_' | '@' | '~' | '`' | '!' | '(' | ')' | '{' | '}' | '^'
+        | '#' | '&' => Some(c as u8),
+        _ => None,
+    }
+}
+
+fn short_fit(name: &str) -> Option<[u8; 11]> {
+    let (base, ext) = match name.rsplit_once('.') {
+        Some((base, ext)) => (base, ext),
+        None => (name, ""),
+    };
+    if base.is_empty() || base.len() > 8 || ext.len() > 3 {
+        return None;
+    }
+    let mut short = [b' '; 11];
+    for (i, c) in base.chars().enumerate() {
+        short[i] = short_char(c).filter(|_| !c.is_ascii_lowercase())?;
+    }
+    for (i, c) in ext.chars().enumerate() {
+        short[8 + i] = short_char(c).filter(|_| !c.is_ascii_lowercase())?;
+    }
+    Some(short)
+}
+
+fn short_generate(name: &str, exists: impl Fn(&[u8; 11]) -> bool) -> Option<[u8; 11]> {
+    let name = name.trim_start_matches('.');
+    let (base, ext) = match name.rsplit_once('.') {
+        Some((base, ext)) => (base, ext),
+        None => (name, ""),
+    };
+    let map = |s: &str| {
+        s.chars()
+            .filter(|&c| c != ' ' && c != '.')
+            .map(|c| short_char(c).unwrap_or(b'_'))
+            .collect::<Vec<u8>>()
+    };
+    let base = map(base);
+    let ext = map(ext);
+    let mut short = [b' '; 11];
+    for (i, &c) in ext.iter().take(3).enumerate() {
+        short[8 + i] = c;
+    }
+    for n in 1..1000000u32 {
+        let tail = format!("~{}", n);
+        let keep = usize::min(base.len(), 8 - tail.len());
+        short[..8].fill(b' ');
+        short[..keep].copy_from_slice(&base[..keep]);
+        short[keep..keep + tail.len()].copy_from_slice(tail.as_bytes());
+        if !exists(&short) {
+            return Some(short);
+        }
+    }
+    None
+}
+
+fn short_display(entry: &[u8]) -> String {
+    let mut short = [0u8; 11];
+    short.copy_from_slice(&entry[0..11]);
+    if short[0] == 0x05 {
+        short[0] = 0xe5;
+    }
+    let lower = |s: &[u8], flag: bool| {
+        s.iter()
+            .map(|&c| if flag { c.to_ascii_lowercase() } else { c })
+            .map(char::from)
+            .collect::<String>()
+    };
+    let base = lower(&short[0..8], entry[12] & 0x08 != 0);
+    let ext = lower(&short[8..11], entry[12] & 0x10 != 0);
+    let base = base.trim_end_matches(' ');
+    let ext = ext.trim_end_matches(' ');
+    if ext.is_empty() {
+        base.to_string()
+    } else {
+        format!("{}.{}", base, ext)
+    }
+}
+
+fn check(name: &str) -> Result<(), FsError> {
+    ensure!(
+        !name.is_empty() && name != "." && name != "..",
+        FsError::BadPath
+    );
+    ensure!(name.encode_utf16().count() <= 255, FsError::BadPath);
+    ensure!(
+        !name.ends_with('.') && !name.ends_with(' '),
+        FsError::BadPath
+    );
+    for c in name.chars() {
+        ensure!(!(c < ' ' || "\"*/:<>?\\|".contains(c)), FsError::BadPath);
+    }
+    Ok(())
+}
+
+struct Fat32Inner {
+    device: Arc<dyn BlockDevice>,
+    read_only: bool,
+    cluster_size: u64,
+    fat_start: u64,
+    fat_size: u64,
+    fats: u64,
+    data_start: u64,
+    clusters: u32,
+    root_cluster: u32,
+    hint: AsyncMutex<u32>,
+    inodes: Mutex<BTreeMap<u64, Weak<Fat32Inode>>>,
+}
+
+impl Fat32Inner {
+    async fn read(&self, offset: u64, buffer: &mut [u8]) -> Result<(), FsError> {
+        Ok(cache().read(&self.device, offset, buffer).await?)
+    }
+    async fn write(&self, offset: u64, buffer: &[u8]) -> Result<(), FsError> {
+        ensure!(!self.read_only, FsError::ReadOnly);
+        Ok(cache().write(&self.device, offset, buffer).await?)
+    }
+    fn valid(&self, cluster: u32) -> bool {
+        2 <= cluster && cluster - 2 < self.clusters
+    }
+    fn cluster(&self, cluster: u32) -> u64 {
+        self.data_start + (cluster - 2) as u64 * self.cluster_size
+    }
+    async fn fat_get(&self, cluster: u32) -> Result<u32, FsError> {
+        let mut buffer = [0u8; 4];
+        self.read(self.fat_start + cluster as u64 * 4, &mut buffer)
+            .await?;
+        Ok(u32::from_le_bytes(buffer) & FAT_MASK)
+    }
+    async fn fat_set(&self, cluster: u32, value: u32) -> Result<(), FsError> {
+        for i in 0..self.fats {
+            let offset = self.fat_start + i * self.fat_size + cluster as u64 * 4;
+            let mut buffer = [0u8; 4];
+            self.read(offset, &mut buffer).await?;
+            let value = (u32::from_le_bytes(buffer) & !FAT_MASK) | (value & FAT_MASK);
+            self.write(offset, &value.to_le_bytes()).await?;
+        }
+        Ok(())
+    }
+    async fn chain(&self, first: u32) -> Result<Vec<u32>, FsError> {
+        let mut chain = Vec::new();
+        let mut cluster = first;
+        while cluster != 0 {
+            ensure!(self.valid(cluster), FsError::BadFileSystem);
+            ensure!(chain.len() < self.clusters as usize, FsError::BadFileSystem);
+            chain.push(cluster);
+            cluster = match self.fat_get(cluster).await? {
+                next if next >= FAT_LAST => 0,
+                next => next,
+            };
+        }
+        Ok(chain)
+    }
+    async fn alloc(&self, prev: Option<u32>) -> Result<u32, FsError> {
+        ensure!(!self.read_only, FsError::ReadOnly);
+        let mut hint = self.hint.lock().await;
+        for i in 0..self.clusters {
+            let cluster = 2 + (*hint - 2 + i) % self.clusters;
+            if self.fat_get(cluster).await? == 0 {
+                self.fat_set(cluster, FAT_EOC).await?;
+                if let Some(prev) = prev {
+                    self.fat_set(prev, cluster).await?;
+                }
+                *hint = 2 + (cluster - 2 + 1) % self.clusters;
+                drop(hint);
+                let zeroes = vec![0u8; self.cluster_size as usize];
+                self.write(self.cluster(cluster), &zeroes).await?;
+                return Ok(cluster);
+            }
+        }
+        Err(FsError::NoSpace)
+    }
+    async fn free(&self, clusters: &[u32]) -> Result<(), FsError> {
+        for &cluster in clusters {
+            self.fat_set(cluster, 0).await?;
+        }
+        Ok(())
+    }
+    fn locate(&self, chain: &[u32], offset: u64) -> u64 {
+        let index = (offset / self.cluster_size) as usize;
+        self.cluster(chain[index]) + offset % self.cluster_size
+    }
+    async fn read_chain(
+        &self,
+        chain: &[u32],
+        mut offset: u64,
+        buffer: &mut [u8],
+    ) -> Result<(), FsError> {
+        let mut done = 0;
+        while done < buffer.len() {
+            let inside = offset % self.cluster_size;
+            let len = usize::min(buffer.len() - done, (self.cluster_size - inside) as usize);
+            self.read(self.locate(chain, offset), &mut buffer[done..done + len])
+                .await?;
+            done += len;
+            offset += len as u64;
+        }
+        Ok(())
+    }
+    async fn write_chain(
+        &self,
+        chain: &[u32],
+        mut offset: u64,
+        buffer: &[u8],
+    ) -> Result<(), FsError> {
+        let mut done = 0;
+        while done < buffer.len() {
+            let inside = offset % self.cluster_size;
+            let len = usize::min(buffer.len() - done, (self.cluster_size - inside) as usize);
+            self.write(self.locate(chain, offset), &buffer[done..done + len])
+                .await?;
+            done += len;
+            offset += len as u64;
+        }
+        Ok(())
+    }
+}
+
+struct Fat32Entry {
+    name: String,
+    short: [u8; 11],
+    attr: u8,
+    first: u32,
+    size: u32,
+    offset: u64,
+    slots: Vec<u64>,
+}
+
+impl Fat32Entry {
+    fn typa(&self) -> InodeType {
+        if self.attr & ATTR_DIRECTORY != 0 {
+            InodeType::Directory
+        } else {
+            InodeType::File
+        }
+    }
+    fn dot(&self) -> bool {
+        self.name == "." || self.name == ".."
+    }
+}
+
+struct Fat32Scan {
+    chain: Vec<u32>,
+    raw: Vec<u8>,
+    entries: Vec<Fat32Entry>,
+}
+
+pub struct Fat32Inode {
+    fs: Arc<Fat32Inner>,
+    entry: Option<u64>,
+    typa: InodeType,
+    first: AtomicU32,
+    size: AtomicU32,
+    lock: AsyncMutex<()>,
+}
+
+impl Fat32Inode {
+    fn get(fs: &Arc<Fat32Inner>, entry: &Fat32Entry) -> Arc<Fat32Inode> {
+        let mut inodes = fs.inodes.lock();
+        if let Some(inode) = inodes.get(&entry.offset).and_then(Weak::upgrade) {
+            return inode;
+        }
+        inodes.retain(|_, inode| inode.strong_count() != 0);
+        let inode = Arc::new(Fat32Inode {
+            fs: fs.clone(),
+            entry: Some(entry.offset),
+            typa: entry.typa(),
+            first: AtomicU32::new(entry.first),
+            size: AtomicU32::new(entry.size),
+            lock: AsyncMutex::new(()),
+        });
+        inodes.insert(entry.offset, Arc::downgrade(&inode));
+        inode
+    }
+    fn first(&self) -> u32 {
+        self.first.load(Ordering::Relaxed)
+    }
+    fn size(&self) -> u64 {
+        self.size.load(Ordering::Relaxed) as u64
+    }
+    async fn update(&self) -> Result<(), FsError> {
+        if let Some(entry) = self.entry {
+            let first = self.first();
+            let size = match self.typa {
+                InodeType::File => self.size.load(Ordering::Relaxed),
+                InodeType::Directory => 0,
+            };
+            let mut low = [0u8; 6];
+            set_u16_at(&mut low, 0, first as u16);
+            set_u32_at(&mut low, 2, size);
+            let mut high = [0u8; 2];
+            set_u16_at(&mut high, 0, (first >> 16) as u16);
+            self.fs.write(entry + 20, &high).await?;
+            self.fs.write(entry + 26, &low).await?;
+        }
+        Ok(())
+    }
+    async fn grow(&self, chain: &mut Vec<u32>, count: usize) -> Result<(), FsError> {
+        while chain.len() < count {
+            let cluster = self.fs.alloc(chain.last().copied()).await?;
+            if chain.is_empty() {
+                self.first.store(cluster, Ordering::Relaxed);
+            }
+            chain.push(cluster);
+        }
+        Ok(())
+    }
+    async fn shrink(&self, chain: &mut Vec<u32>, keep: usize) -> Result<(), FsError> {
+        if keep < chain.len() {
+            if keep == 0 {
+                self.first.store(0, Ordering::Relaxed);
+            } else {
+                self.fs.fat_set(chain[keep - 1], FAT_EOC).await?;
+            }
+            self.update().await?;
+            self.fs.free(&chain[keep..]).await?;
+            chain.truncate(keep);
+        }
+        Ok(())
+    }
+    async fn zero_tail(&self, chain: &[u32], from: u64, to: u64) -> Result<(), FsError> {
+        let to = u64::min(to, from.next_multiple_of(self.fs.cluster_size));
+        if from < to && from % self.fs.cluster_size != 0 {
+            let zeroes = vec![0u8; (to - from) as usize];
+            self.fs.write_chain(chain, from, &zeroes).await?;
+        }
+        Ok(())
+    }
+    async fn scan(&self) -> Result<Fat32Scan, FsError> {
+        let chain = self.fs.chain(self.first()).await?;
+        let mut raw = vec![0u8; chain.len() * self.fs.cluster_size as usize];
+        self.fs.read_chain(&chain, 0, &mut raw).await?;
+        let mut entries = Vec::new();
+        let mut long: Option<(u8, u8, Vec<u16>, Vec<u64>)> = None;
+        for (i, slot) in raw.chunks_exact(ENTRY_SIZE).enumerate() {
+            let offset = self.fs.locate(&chain, (i * ENTRY_SIZE) as u64);
+            match slot[0] {
+                0x00 => break,
+                0xe5 => {
+                    long = None;
+                    continue;
+                }
+                _ => (),
+            }
+            if slot[11] == ATTR_LONG_NAME {
+                let order = slot[0] & 0x1f;
+                if slot[0] & 0x40 != 0 {
+                    long = Some((
+                        order,
+                        slot[13],
+                        vec![0xffff; 13 * order as usize],
+                        Vec::new(),
+                    ));
+                }
+                long =
+                    long.filter(|&(next, sum, ..)| next == order && sum == slot[13] && order != 0);
+                if let Some((next, _, units, slots)) = long.as_mut() {
+                    let base = 13 * (order as usize - 1);
+                    for (unit, &offset) in
+                        units[base..base + 13].iter_mut().zip(LONG_OFFSETS.iter())
+                    {
+                        *unit = u16_at(slot, offset);
+                    }
+                    slots.push(offset);
+                    *next -= 1;
+                }
+                continue;
+            }
+            let long = long.take();
+            if slot[11] & ATTR_VOLUME_ID != 0 {
+                continue;
+            }
+            let mut short = [0u8; 11];
+            short.copy_from_slice(&slot[0..11]);
+            let (name, mut slots) = match long {
+                Some((0, sum, units, slots)) if sum == checksum(&short) => {
+                    let len = units.iter().position(|&c| c == 0).unwrap_or(units.len());
+                    let name = char::decode_utf16(units[..len].iter().copied())
+                        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                        .collect::<String>();
+                    (name, slots)
+                }
+                _ => (short_display(slot), Vec::new()),
+            };
+            slots.push(offset);
+            entries.push(Fat32Entry {
+                name,
+                short,
+                attr: slot[11],
+                first: (u16_at(slot, 20) as u32) << 16 | u16_at(slot, 26) as u32,
+                size: u32_at(slot, 28),
+                offset,
+                slots,
+            });
+        }
+        Ok(Fat32Scan {
+            chain,
+            raw,
+            entries,
+        })
+    }
+    fn find<'a>(scan: &'a Fat32Scan, name: &str) -> Option<&'a Fat32Entry> {
+        scan.entries
+            .iter()
+            .filter(|entry| !entry.dot())
+            .find(|entry| entry.name.eq_ignore_ascii_case(name))
+    }
+    async fn insert(
+        &self,
+        scan: &mut Fat32Scan,
+        name: &str,
+        attr: u8,
+        first: u32,
+    ) -> Result<Fat32Entry, FsError> {
+        let fit =
+            short_fit(name).filter(|short| !scan.entries.iter().any(|entry| &entry.short == short));
+        let short = match fit {
+            Some(short) => short,
+            None => short_generate(name, |short| {
+                scan.entries.iter().any(|entry| &entry.short == short)
+            })
+            .ok_or(FsError::NoSpace)?,
+        };
+        let units = if fit.is_some() {
+            Vec::new()
+        } else {
+            name.encode_utf16().collect::<Vec<u16>>()
+        };
+        let longs = units.len().div_ceil(13);
+        let count = longs + 1;
+        let total = scan.raw.len() / ENTRY_SIZE;
+        let mut start = 0;
+        let mut run = 0;
+        for i in 0..total {
+            if run == count {
+                break;
+            }
+            match scan.raw[i * ENTRY_SIZE] {
+                0x00 | 0xe5 => run += 1,
+                _ => {
+                    start = i + 1;
+                    run = 0;
+                }
+            }
+        }
+        let needed = ((start + count) * ENTRY_SIZE) as u64;
+        ensure!(needed as usize <= MAX_DIR_SIZE, FsError::NoSpace);
+        let clusters = needed.div_ceil(self.fs.cluster_size) as usize;
+        self.grow(&mut scan.chain, clusters).await?;
+        let sum = checksum(&short);
+        let mut slots = Vec::new();
+        let mut buffer = vec![0u8; count * ENTRY_SIZE];
+        for (k, slot) in buffer.chunks_exact_mut(ENTRY_SIZE).take(longs).enumerate() {
+            let order = longs - k;
+            let mut part = [0xffffu16; 13];
+            for (j, unit) in part.iter_mut().enumerate() {
+                let index = 13 * (order - 1) + j;
+                if index < units.len() {
+                    *unit = units[index];
+                } else if index == units.len() {
+                    *unit = 0;
+                }
+            }
+            slot[0] = order as u8 | if k == 0 { 0x40 } else { 0 };
+            slot[11] = ATTR_LONG_NAME;
+            slot[13] = sum;
+            for j in 0..5 {
+                set_u16_at(slot, 1 + 2 * j, part[j]);
+            }
+            for j in 0..6 {
+                set_u16_at(slot, 14 + 2 * j, part[5 + j]);
+            }
+            for j in 0..2 {
+                set_u16_at(slot, 28 + 2 * j, part[11 + j]);
+            }
+        }
+        let slot = &mut buffer[longs * ENTRY_SIZE..];
+        slot[0..11].copy_from_slice(&short);
+        slot[11] = attr;
+        set_u16_at(slot, 16, DATE);
+        set_u16_at(slot, 18, DATE);
+        set_u16_at(slot, 20, (first >> 16) as u16);
+        set_u16_at(slot, 24, DATE);
+        set_u16_at(slot, 26, first as u16);
+        let base = (start * ENTRY_SIZE) as u64;
+        self.fs.write_chain(&scan.chain, base, &buffer).await?;
+        for i in 0..count {
+            slots.push(self.fs.locate(&scan.chain, base + (i * ENTRY_SIZE) as u64));
+        }
+        Ok(Fat32Entry {
+            name: name.to_string(),
+            short,
+            attr,
+            first,
+            size: 0,
+            offset: *slots.last().unwrap(),
+            slots,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Inode for Fat32Inode {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            ino: self.entry.unwrap_or(ROOT_INO),
+            typa: self.typa,
+            size: match self.typa {
+                InodeType::File => self.size(),
+                InodeType::Directory => 0,
+            },
+        }
+    }
+    async fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        ensure!(self.typa == InodeType::File, FsError::IsADirectory);
+        let _guard = self.lock.lock().await;
+        let size = self.size();
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = u64::min(buffer.len() as u64, size - offset) as usize;
+        let chain = self.fs.chain(self.first()).await?;
+        ensure!(
+            chain.len() as u64 * self.fs.cluster_size >= size,
+            FsError::BadFileSystem
+        );
+        self.fs
+            .read_chain(&chain, offset, &mut buffer[..len])
+            .await?;
+        Ok(len)
+    }
+    async fn write_at(&self, offset: u64, buffer: &[u8]) -> Result<usize, FsError> {
+        ensure!(self.typa == InodeType::File, FsError::IsADirectory);
+        ensure!(!self.fs.read_only, FsError::ReadOnly);
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+        let _guard = self.lock.lock().await;
+        let size = self.size();
+        let end = offset
+            .checked_add(buffer.len() as u64)
+            .filter(|&end| end <= u32::MAX as u64)
+            .ok_or(FsError::NoSpace)?;
+        let mut chain = self.fs.chain(self.first()).await?;
+        let keep = chain.len();
+        let mut result = self
+            .grow(&mut chain, end.div_ceil(self.fs.cluster_size) as usize)
+            .await;
+        if result.is_ok() {
+            result = self.zero_tail(&chain, size, offset).await;
+        }
+        if result.is_ok() {
+            result = self.fs.write_chain(&chain, offset, buffer).await;
+        }
+        match result {
+            Ok(()) => {
+                if end > size {
+                    self.size.store(end as u32, Ordering::Relaxed);
+                }
+                self.update().await?;
+                Ok(buffer.len())
+            }
+            Err(e) => {
+                self.shrink(&mut chain, keep).await?;
+                Err(e)
+            }
+        }
+    }
+    async fn truncate(&self, new: u64) -> Result<(), FsError> {
+        ensure!(self.typa == InodeType::File, FsError::IsADirectory);
+        ensure!(!self.fs.read_only, FsError::ReadOnly);
+        ensure!(new <= u32::MAX as u64, FsError::NoSpace);
+        let _guard = self.lock.lock().await;
+        let size = self.size();
+        let mut chain = self.fs.chain(self.first()).await?;
+        let keep = new.div_ceil(self.fs.cluster_size) as usize;
+        self.size
+            .store(u64::min(size, new) as u32, Ordering::Relaxed);
+        self.shrink(&mut chain, keep).await?;
+        if new > size {
+            self.grow(&mut chain, keep).await?;
+            self.zero_tail(&chain, size, new).await?;
+        }
+        self.size.store(new as u32, Ordering::Relaxed);
+        self.update().await
+    }
+    async fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FsError> {
+        ensure!(self.typa == InodeType::Directory, FsError::NotADirectory);
+        let _guard = self.lock.lock().await;
+        let scan = self.scan().await?;
+        let entry = Self::find(&scan, name).ok_or(FsError::NotFound)?;
+        Ok(Fat32Inode::get(&self.fs, entry))
+    }
+    async fn readdir(&self) -> Result<Vec<DirEntry>, FsError> {
+        ensure!(self.typa == InodeType::Directory, FsError::NotADirectory);
+        let _guard = self.lock.lock().await;
+        let scan = self.scan().await?;
+        Ok(scan
+            .entries
+            .iter()
+            .filter(|entry| !entry.dot())
+            .map(|entry| DirEntry {
+                name: entry.name.clone(),
+                ino: entry.offset,
+                typa: entry.typa(),
+            })
+            .collect())
+    }
+    async fn create(&self, name: &str, typa: InodeType) -> Result<Arc<dyn Inode>, FsError> {
+        ensure!(self.typa == InodeType::Directory, FsError::NotADirectory);
+        ensure!(!self.fs.read_only, FsError::ReadOnly);
+        check(name)?;
+        let _guard = self.lock.lock().await;
+        let mut scan = self.scan().await?;
+        ensure!(Self::find(&scan, name).is_none(), FsError::AlreadyExists);
+        let (attr, first) = match typa {
+            InodeType::File => (ATTR_ARCHIVE, 0),
+            InodeType::Directory => {
+                let first = self.fs.alloc(None).await?;
+                let parent = match self.entry {
+                    Some(_) => self.first(),
+                    None => 0,
+                };
+                let mut dots = [0u8; 2 * ENTRY_SIZE];
+                for (slot, (name, cluster)) in dots
+                    .chunks_exact_mut(ENTRY_SIZE)
+                    .zip([(b".          ", first), (b"..         ", parent)])
+                {
+                    slot[0..11].copy_from_slice(name);
+                    slot[11] = ATTR_DIRECTORY;
+                    set_u16_at(slot, 16, DATE);
+                    set_u16_at(slot, 18, DATE);
+                    set_u16_at(slot, 20, (cluster >> 16) as u16);
+                    set_u16_at(slot, 24, DATE);
+                    set_u16_at(slot, 26, cluster as u16);
+                }
+                self.fs.write(self.fs.cluster(first), &dots).await?;
+                (ATTR_DIRECTORY, first)
+            }
+        };
+        match self.insert(&mut scan, name, attr, first).await {
+            Ok(entry) => Ok(Fat32Inode::get(&self.fs, &entry)),
+            Err(e) => {
+                if first != 0 {
+                    self.fs.free(&[first]).await?;
+                }
+                Err(e)
+            }
+        }
+    }
+    async fn unlink(&self, name: &str) -> Result<(), FsError> {
+        ensure!(self.typa == InodeType::Directory, FsError::NotADirectory);
+        ensure!(!self.fs.read_only, FsError::ReadOnly);
+        let _guard = self.lock.lock().await;
+        let scan = self.scan().await?;
+        let entry = Self::find(&scan, name).ok_or(FsError::NotFound)?;
+        let inode = Fat32Inode::get(&self.fs, entry);
+        ensure!(Arc::strong_count(&inode) == 1, FsError::Busy);
+        if entry.typa() == InodeType::Directory {
+            let children = inode.scan().await?;
+            ensure!(
+                children.entries.iter().all(Fat32Entry::dot),
+                FsError::NotEmpty
+            );
+        }
+        for &slot in entry.slots.iter() {
+            self.fs.write(slot, &[0xe5]).await?;
+        }
+        self.fs.inodes.lock().remove(&entry.offset);
+        let chain = self.fs.chain(entry.first).await?;
+        self.fs.free(&chain).await
+    }
+    async fn sync(&self) -> Result<(), FsError> {
+        Ok(cache().sync(Some(&self.fs.device)).await?)
+    }
+}
+
+pub struct Fat32 {
+    inner: Arc<Fat32Inner>,
+    root: Arc<Fat32Inode>,
+}
+
+impl Fat32 {
+    pub async fn mount(device: Arc<dyn BlockDevice>) -> Result<Arc<Fat32>, FsError> {
+        use FsError::*;
+        let mut boot = [0u8; 512];
+        cache()
+            .read(&device, 0, &mut boot)
+            .await
+            .map_err(|_| BadFileSystem)?;
+        ensure!(boot[510..512] == [0x55, 0xaa], BadFileSystem);
+        let bytes_per_sector = u16_at(&boot, 11) as u64;
+        let sectors_per_cluster = boot[13] as u64;
+        let reserved = u16_at(&boot, 14) as u64;
+        let fats = boot[16] as u64;
+        let root_entries = u16_at(&boot, 17);
+        let total = match u16_at(&boot, 19) {
+            0 => u32_at(&boot, 32) as u64,
+            total => total as u64,
+        };
+        let fat_size = u32_at(&boot, 36) as u64;
+        let root_cluster = u32_at(&boot, 44);
+        ensure!(
+            [512, 1024, 2048, 4096].contains(&bytes_per_sector),
+            BadFileSystem
+        );
+        ensure!(
+            sectors_per_cluster.is_power_of_two() && reserved != 0 && fats != 0,
+            BadFileSystem
+        );
+        ensure!(
+            root_entries == 0 && u16_at(&boot, 22) == 0 && fat_size != 0,
+            BadFileSystem
+        );
+        let data = reserved + fats * fat_size;
+        ensure!(total > data, BadFileSystem);
+        let clusters = (total - data) / sectors_per_cluster;
+        ensure!(
+            clusters + 2 <= fat_size * bytes_per_sector / 4 && clusters < FAT_LAST as u64,
+            BadFileSystem
+        );
+        ensure!(
+            total * bytes_per_sector <= device.capacity() * device.sector_size() as u64,
+            BadFileSystem
+        );
+        let inner = Arc::new(Fat32Inner {
+            read_only: device.read_only(),
+            device,
+            cluster_size: sectors_per_cluster * bytes_per_sector,
+            fat_start: reserved * bytes_per_sector,
+            fat_size: fat_size * bytes_per_sector,
+            fats,
+            data_start: data * bytes_per_sector,
+            clusters: clusters as u32,
+            root_cluster,
+            hint: AsyncMutex::new(2),
+            inodes: Mutex::new(BTreeMap::new()),
+        });
+        ensure!(inner.valid(root_cluster), BadFileSystem);
+        let fsinfo = u16_at(&boot, 48) as u64;
+        if !inner.read_only && fsinfo != 0 && fsinfo < reserved {
+            let offset = fsinfo * bytes_per_sector;
+            let mut info = [0u8; 512];
+            inner.read(offset, &mut info).await?;
+            if u32_at(&info, 0) == 0x41615252 && u32_at(&info, 484) == 0x61417272 {
+                inner.write(offset + 488, &[0xff; 8]).await?;
+            }
+        }
+        let root = Arc::new(Fat32Inode {
+            fs: inner.clone(),
+            entry: None,
+            typa: InodeType::Directory,
+            first: AtomicU32::new(inner.root_cluster),
+            size: AtomicU32::new(0),
+            lock: AsyncMutex::new(()),
+        });
+        Ok(Arc::new(Fat32 { inner, root }))
+    }
+}
+
+#[async_trait::async_trait]
+impl FileSystem for Fat32 {
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+    async fn sync(&self) -> Result<(), FsError> {
+        Ok(cache().sync(Some(&self.inner.device)).await?)
+    }
+}
@@ -0,0 +1,129 @@
+use crate::prelude::*;
+use alloc::format;
+use fs::memfs::MemFs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitrdError {
+    UnknownFormat,
+    BadArchive,
+    BadPath,
+}
+
+const CPIO_HEADER: usize = 110;
+const TAR_BLOCK: usize = 512;
+
+pub fn unpack(memfs: &MemFs, archive: &'static [u8]) -> Result<usize, InitrdError> {
+    if archive.starts_with(b"070701") || archive.starts_with(b"070702") {
+        unpack_cpio(memfs, archive)
+    } else if archive.len() >= TAR_BLOCK && &archive[257..262] == b"ustar" {
+        unpack_tar(memfs, archive)
+    } else {
+        Err(InitrdError::UnknownFormat)
+    }
+}
+
+fn insert(memfs: &MemFs, name: &[u8], content: Option<&'static [u8]>) -> Result<bool, InitrdError> {
+    let name = core::str::from_utf8(name).map_err(|_| InitrdError::BadPath)?;
+    let path = format!("/{}", name);
+    let components = fs::vfs::normalize(&path).map_err(|_| InitrdError::BadPath)?;
+    if components.is_empty() {
+        return Ok(false);
+    }
+    match memfs.insert(&path, content) {
+        Ok(()) => Ok(true),
+        Err(FsError::BadPath) => Err(InitrdError::BadPath),
+        Err(e) => {
+            warn!("skip initrd entry {}, reason = {:?}", path, e);
+            Ok(false)
+        }
+    }
+}
+
+fn hex(field: &[u8]) -> Result<usize, InitrdError> {
+    let s = core::str::from_utf8(field).map_err(|_| InitrdError::BadArchive)?;
+    usize::from_str_radix(s, 16).map_err(|_| InitrdError::BadArchive)
+}
+
+fn octal(field: &[u8]) -> Result<usize, InitrdError> {
+    let s = core::str::from_utf8(field).map_err(|_| InitrdError::BadArchive)?;
+    let s = s.trim_matches(|c| c == '\0' || c == ' ');
+    if s.is_empty() {
+        return Ok(0);
+    }
+    usize::from_str_radix(s, 8).map_err(|_| InitrdError::BadArchive)
+}
+
+fn cstr(field: &[u8]) -> &[u8] {
+    match field.iter().position(|&c| c == 0) {
+        Some(len) => &field[..len],
+        None => field,
+    }
+}
+
+fn slice(archive: &'static [u8], start: usize, len: usize) -> Result<&'static [u8], InitrdError> {
+    let end = start.checked_add(len).ok_or(InitrdError::BadArchive)?;
+    archive.get(start..end).ok_or(InitrdError::BadArchive)
+}
+
+fn unpack_cpio(memfs: &MemFs, archive: &'static [u8]) -> Result<usize, InitrdError> {
+    let mut count = 0;
+    let mut ptr = 0;
+    loop {
+        let header = slice(archive, ptr, CPIO_HEADER)?;
+        ensure!(
+            &header[0..6] == b"070701" || &header[0..6] == b"070702",
+            InitrdError::BadArchive
+        );
+        let mode = hex(&header[14..22])?;
+        let file_size = hex(&header[54..62])?;
+        let name_size = hex(&header[94..102])?;
+        ensure!(name_size != 0, InitrdError::BadArchive);
+        let name = cstr(slice(archive, ptr + CPIO_HEADER, name_size)?);
+        let data = (ptr + CPIO_HEADER + name_size).next_multiple_of(4);
+        let content = slice(archive, data, file_size)?;
+        if name == b"TRAILER!!!" {
+            break;
+        }
+        let inserted = match mode & 0o170000 {
+            0o040000 => insert(memfs, name, None)?,
+            0o100000 => insert(memfs, name, Some(content))?,
+            _ => false,
+        };
+        if inserted {
+            count += 1;
+        }
+        ptr = (data + file_size).next_multiple_of(4);
+    }
+    Ok(count)
+}
+
+fn unpack_tar(memfs: &MemFs, archive: &'static [u8]) -> Result<usize, InitrdError> {
+    let mut count = 0;
+    let mut ptr = 0;
+    while ptr + TAR_BLOCK <= archive.len() {
+        let header = &archive[ptr..ptr + TAR_BLOCK];
+        if header.iter().all(|&c| c == 0) {
+            break;
+        }
+        ensure!(&header[257..262] == b"ustar", InitrdError::BadArchive);
+        let size = octal(&header[124..136])?;
+        let content = slice(archive, ptr + TAR_BLOCK, size)?;
+        let prefix = cstr(&header[345..500]);
+        let name = cstr(&header[0..100]);
+        let path = if prefix.is_empty() {
+            name.to_vec()
+        } else {
+            [prefix, &b"/"[..], name].concat()
+        };
+        let inserted = match header[156] {
+            b'5' => insert(memfs, &path, None)?,
+            b'0' | b'\0' => insert(memfs, &path, Some(content))?,
+            _ => false,
+        };
+        if inserted {
+            count += 1;
+        }
+        ptr += TAR_BLOCK + size.next_multiple_of(TAR_BLOCK);
+    }
+    Ok(count)
+}
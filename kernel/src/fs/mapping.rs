@@ -0,0 +1,170 @@
+use crate::prelude::*;
+use fs::cache::CachedFile;
+use proc::vmm::MapUser;
+use rt::paging::Paging;
+use spin::Mutex;
+use user::objects::memory::Memory;
+
+struct FileMappingSlot {
+    page: Option<Arc<Memory>>,
+    installed: Option<(Arc<<P as Platform>::Paging>, VAddr)>,
+}
+
+pub struct FileMapping {
+    file: Arc<CachedFile>,
+    first: u64,
+    layout: MapLayout,
+    writable: bool,
+    slots: Mutex<Vec<FileMappingSlot>>,
+}
+
+impl FileMapping {
+    pub fn new(
+        inode: &Arc<dyn Inode>,
+        offset: u64,
+        size: usize,
+        writable: bool,
+    ) -> Option<Arc<Self>> {
+        let m = config::PAGE_CACHE_LAYOUT.size();
+        if offset % m as u64 != 0 || size == 0 {
+            return None;
+        }
+        let layout = MapLayout::new(size.checked_next_multiple_of(m)?, m)?;
+        let slots = (0..layout.size() / m)
+            .map(|_| FileMappingSlot {
+                page: None,
+                installed: None,
+            })
+            .collect();
+        Some(Arc::new(Self {
+            file: fs::cache::cache().file(inode),
+            first: offset / m as u64,
+            layout,
+            writable,
+            slots: Mutex::new(slots),
+        }))
+    }
+    fn page(&self, i: usize) -> Option<Arc<Memory>> {
+        let mut slots = self.slots.lock();
+        if slots[i].page.is_none() {
+            slots[i].page = self.file.cached(self.first + i as u64);
+        }
+        slots[i].page.clone()
+    }
+}
+
+impl Map for FileMapping {
+    fn layout(&self) -> MapLayout {
+        self.layout
+    }
+}
+
+impl MapRead for FileMapping {
+    unsafe fn read_unchecked(&self, offset: usize, buffer: &mut [u8]) {
+        let m = self.layout.align();
+        let mut ptr = offset;
+        while ptr < offset + buffer.len() {
+            let r = usize::min((ptr | (m - 1)) + 1, offset + buffer.len());
+            let buffer = &mut buffer[ptr - offset..r - offset];
+            match self.page(ptr / m) {
+                Some(page) => page.read_unchecked(ptr & (m - 1), buffer),
+                None => buffer.fill(0),
+            }
+            ptr = r;
+        }
+    }
+}
+
+impl MapWrite for FileMapping {
+    unsafe fn write_unchecked(&self, offset: usize, buffer: &[u8]) {
+        let m = self.layout.align();
+        let mut ptr = offset;
+        while ptr < offset + buffer.len() {
+            let r = usize::min((ptr | (m - 1)) + 1, offset + buffer.len());
+            if let Some(page) = self.page(ptr / m) {
+                page.write_unchecked(ptr & (m - 1), &buffer[ptr - offset..r - offset]);
+            }
+            ptr = r;
+        }
+    }
+}
+
+impl MapIndex for FileMapping {
+    unsafe fn index_unchecked(&self, i: usize) -> PAddr {
+        self.page(i)
+            .expect("the page is indexed before it is installed")
+            .index(0)
+    }
+}
+
+#[async_trait::async_trait]
+impl MapUser for FileMapping {
+    fn present(&self, i: usize) -> bool {
+        self.slots.lock()[i].installed.is_some()
+    }
+    fn filled(&self, i: usize) -> bool {
+        self.page(i).is_some()
+    }
+    fn install(
+        &self,
+        i: usize,
+        page_table: &Arc<<P as Platform>::Paging>,
+        vaddr: VAddr,
+    ) -> Option<PAddr> {
+        let mut slots = self.slots.lock();
+        let slot = &mut slots[i];
+        if slot.installed.is_some() {
+            return None;
+        }
+        let paddr = slot.page.as_ref()?.index(0);
+        slot.installed = Some((page_table.clone(), vaddr));
+        if self.writable {
+            self.file
+                .map_writable(self.first + i as u64, page_table, vaddr);
+        }
+        Some(paddr)
+    }
+    fn uninstall(&self, i: usize, dirty: bool) {
+        let (page_table, vaddr) = self.slots.lock()[i].installed.take().unwrap();
+        if self.writable {
+            self.file
+                .unmap_writable(self.first + i as u64, &page_table, vaddr, dirty);
+        }
+    }
+    async fn fill(&self, i: usize) -> Result<(), FsError> {
+        if self.slots.lock()[i].page.is_some() {
+            return Ok(());
+        }
+        let page = self.file.page(self.first + i as u64).await?;
+        self.slots.lock()[i].page.get_or_insert(page);
+        Ok(())
+    }
+    async fn sync(&self) -> Result<(), FsError> {
+        if self.writable {
+            self.file.write_back().await?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FileMapping {
+    fn drop(&mut self) {
+        if !self.writable {
+            return;
+        }
+        for (i, slot) in self.slots.get_mut().iter_mut().enumerate() {
+            if let Some((page_table, vaddr)) = slot.installed.take() {
+                let paddr = slot.page.as_ref().unwrap().index(0);
+                let dirty = page_table.clean(vaddr, paddr, self.layout.align());
+                self.file
+                    .unmap_writable(self.first + i as u64, &page_table, vaddr, dirty);
+            }
+        }
+        let file = self.file.clone();
+        sched::scheduler::detach(async move {
+            if let Err(e) = file.write_back().await {
+                warn!("write back of a file mapping failed: {:?}", e);
+            }
+        });
+    }
+}
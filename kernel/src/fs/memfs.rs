@@ -1,25 +1,136 @@
+use crate::prelude::*;
 use alloc::collections::BTreeMap;
-use spin::Lazy;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::{Lazy, RwLock};
 
 pub struct MemFs {
-    data: BTreeMap<&'static str, &'static [u8]>,
+    root: Arc<MemFsInode>,
+    next: AtomicU64,
+}
+
+pub struct MemFsInode {
+    ino: u64,
+    content: Option<&'static [u8]>,
+    children: RwLock<BTreeMap<String, Arc<MemFsInode>>>,
 }
 
 impl MemFs {
     pub fn new() -> Self {
-        let mut data = BTreeMap::new();
-        data.insert(
-            "initproc",
-            include_bytes!(env!("memfs_initproc")).as_slice(),
-        );
-        Self { data }
+        let this = Self {
+            root: Arc::new(MemFsInode {
+                ino: 1,
+                content: None,
+                children: RwLock::new(BTreeMap::new()),
+            }),
+            next: AtomicU64::new(2),
+        };
+        this.insert(
+            "/initproc",
+            Some(include_bytes!(env!("memfs_initproc")).as_slice()),
+        )
+        .unwrap();
+        #[cfg(memfs_initrd)]
+        this.unpack("embedded", include_bytes!(env!("memfs_initrd")));
+        if let Some(initrd) = rt::mem::memory().initrd {
+            let size = initrd.wrapping_end() - initrd.start();
+            let archive = unsafe { core::slice::from_raw_parts(initrd.start().to_const(), size) };
+            this.unpack("boot", archive);
+        }
+        this
+    }
+    fn unpack(&self, source: &str, archive: &'static [u8]) {
+        match fs::initrd::unpack(self, archive) {
+            Ok(count) => info!("{} initrd is unpacked with {} entries", source, count),
+            Err(e) => error!("{} initrd is broken: {:?}", source, e),
+        }
+    }
+    pub fn insert(&self, path: &str, content: Option<&'static [u8]>) -> Result<(), FsError> {
+        let mut components = fs::vfs::normalize(path)?;
+        let name = components.pop().ok_or(FsError::BadPath)?;
+        let mut current = self.root.clone();
+        for name in components {
+            ensure!(current.content.is_none(), FsError::NotADirectory);
+            let next = current
+                .children
+                .write()
+                .entry(name.to_string())
+                .or_insert_with(|| self.node(None))
+                .clone();
+            current = next;
+        }
+        ensure!(current.content.is_none(), FsError::NotADirectory);
+        let mut children = current.children.write();
+        match children.get(name) {
+            Some(node) if node.content.is_none() && content.is_none() => (),
+            Some(_) => return Err(FsError::AlreadyExists),
+            None => {
+                children.insert(name.to_string(), self.node(content));
+            }
+        }
+        Ok(())
+    }
+    fn node(&self, content: Option<&'static [u8]>) -> Arc<MemFsInode> {
+        Arc::new(MemFsInode {
+            ino: self.next.fetch_add(1, Ordering::Relaxed),
+            content,
+            children: RwLock::new(BTreeMap::new()),
+        })
+    }
+}
+
+impl MemFsInode {
+    fn typa(&self) -> InodeType {
+        match self.content {
+            Some(_) => InodeType::File,
+            None => InodeType::Directory,
+        }
     }
-    pub fn read(&self, index: &str) -> Option<&[u8]> {
-        self.data.get(index).cloned()
+}
+
+#[async_trait::async_trait]
+impl Inode for MemFsInode {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            ino: self.ino,
+            typa: self.typa(),
+            size: self.content.map_or(0, |content| content.len() as u64),
+        }
+    }
+    async fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        let content = self.content.ok_or(FsError::IsADirectory)?;
+        let offset = usize::min(offset as usize, content.len());
+        let len = usize::min(buffer.len(), content.len() - offset);
+        buffer[..len].copy_from_slice(&content[offset..offset + len]);
+        Ok(len)
+    }
+    async fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FsError> {
+        ensure!(self.content.is_none(), FsError::NotADirectory);
+        let children = self.children.read();
+        let node = children.get(name).ok_or(FsError::NotFound)?;
+        Ok(node.clone())
+    }
+    async fn readdir(&self) -> Result<Vec<DirEntry>, FsError> {
+        ensure!(self.content.is_none(), FsError::NotADirectory);
+        Ok(self
+            .children
+            .read()
+            .iter()
+            .map(|(name, node)| DirEntry {
+                name: name.clone(),
+                ino: node.ino,
+                typa: node.typa(),
+            })
+            .collect())
+    }
+}
+
+impl FileSystem for MemFs {
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
     }
 }
 
-pub fn memfs() -> &'static MemFs {
-    static MEMFS: Lazy<MemFs> = Lazy::new(MemFs::new);
-    &MEMFS
+pub fn memfs() -> Arc<MemFs> {
+    static MEMFS: Lazy<Arc<MemFs>> = Lazy::new(|| Arc::new(MemFs::new()));
+    MEMFS.clone()
 }
@@ -1 +1,10 @@
+pub mod cache;
+pub mod defines;
+pub mod ext2;
+pub mod fat32;
+pub mod initrd;
+pub mod mapping;
 pub mod memfs;
+pub mod tmpfs;
+pub mod utils;
+pub mod vfs;
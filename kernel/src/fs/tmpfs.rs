@@ -0,0 +1,385 @@
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use core::sync::atomic::{AtomicU64, Ordering};
+use proc::vmm::MapUser;
+use spin::{Mutex, RwLock};
+use user::objects::memory::Memory;
+
+static ZEROS: [u8; config::TMPFS_PAGE_LAYOUT.size()] = [0; config::TMPFS_PAGE_LAYOUT.size()];
+
+struct TmpFsShared {
+    next: AtomicU64,
+    directories: Mutex<BTreeMap<u64, Weak<TmpFsInode>>>,
+}
+
+pub struct TmpFs {
+    root: Arc<TmpFsInode>,
+}
+
+struct TmpFsFile {
+    size: u64,
+    pages: BTreeMap<usize, Arc<Memory>>,
+}
+
+enum TmpFsContent {
+    File(RwLock<TmpFsFile>),
+    Directory(RwLock<BTreeMap<String, Arc<TmpFsInode>>>),
+}
+
+pub struct TmpFsInode {
+    ino: u64,
+    shared: Arc<TmpFsShared>,
+    content: TmpFsContent,
+}
+
+pub struct TmpFsMapping {
+    pages: Vec<Arc<Memory>>,
+    layout: MapLayout,
+}
+
+impl TmpFs {
+    pub fn new() -> Arc<Self> {
+        let shared = Arc::new(TmpFsShared {
+            next: AtomicU64::new(1),
+            directories: Mutex::new(BTreeMap::new()),
+        });
+        Arc::new(Self {
+            root: TmpFsInode::new(&shared, InodeType::Directory),
+        })
+    }
+}
+
+impl FileSystem for TmpFs {
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
+
+impl TmpFsFile {
+    fn resize(&mut self, size: u64) -> Result<(), FsError> {
+        ensure!(size <= config::TMPFS_FILE_MAX, FsError::NoSpace);
+        let m = config::TMPFS_PAGE_LAYOUT.size();
+        let count = (size as usize).div_ceil(m);
+        self.pages
+            .retain(|&i, page| i < count || Arc::strong_count(page) > 1);
+        self.zero(u64::min(self.size, size) as usize..u64::max(self.size, size) as usize);
+        self.size = size;
+        Ok(())
+    }
+    fn zero(&self, range: core::ops::Range<usize>) {
+        let m = config::TMPFS_PAGE_LAYOUT.size();
+        for (&i, page) in self.pages.range(range.start / m..range.end.div_ceil(m)) {
+            let start = usize::max(range.start, i * m) - i * m;
+            let end = usize::min(range.end, (i + 1) * m) - i * m;
+            page.write(start, &ZEROS[start..end]);
+        }
+    }
+    fn populate(&mut self, pages: core::ops::Range<usize>) -> Result<(), FsError> {
+        let mut fresh = Vec::new();
+        for i in pages.filter(|i| !self.pages.contains_key(i)) {
+            let memory = Memory::create(config::TMPFS_PAGE_LAYOUT).map_err(|_| FsError::NoSpace)?;
+            memory.write(0, &ZEROS);
+            fresh.push((i, memory));
+        }
+        self.pages.extend(fresh);
+        Ok(())
+    }
+}
+
+impl TmpFsInode {
+    fn new(shared: &Arc<TmpFsShared>, typa: InodeType) -> Arc<Self> {
+        let ino = shared.next.fetch_add(1, Ordering::Relaxed);
+        let content = match typa {
+            InodeType::File => TmpFsContent::File(RwLock::new(TmpFsFile {
+                size: 0,
+                pages: BTreeMap::new(),
+            })),
+            InodeType::Directory => TmpFsContent::Directory(RwLock::new(BTreeMap::new())),
+        };
+        let inode = Arc::new(Self {
+            ino,
+            shared: shared.clone(),
+            content,
+        });
+        if typa == InodeType::Directory {
+            shared
+                .directories
+                .lock()
+                .insert(ino, Arc::downgrade(&inode));
+        }
+        inode
+    }
+    fn typa(&self) -> InodeType {
+        match self.content {
+            TmpFsContent::File(_) => InodeType::File,
+            TmpFsContent::Directory(_) => InodeType::Directory,
+        }
+    }
+    fn file(&self) -> Result<&RwLock<TmpFsFile>, FsError> {
+        match &self.content {
+            TmpFsContent::File(file) => Ok(file),
+            TmpFsContent::Directory(_) => Err(FsError::IsADirectory),
+        }
+    }
+    fn children(&self) -> Result<&RwLock<BTreeMap<String, Arc<TmpFsInode>>>, FsError> {
+        match &self.content {
+            TmpFsContent::File(_) => Err(FsError::NotADirectory),
+            TmpFsContent::Directory(children) => Ok(children),
+        }
+    }
+    fn is_empty(&self) -> bool {
+        match &self.content {
+            TmpFsContent::File(_) => true,
+            TmpFsContent::Directory(children) => children.read().is_empty(),
+        }
+    }
+    fn contains(&self, ino: u64) -> bool {
+        if self.ino == ino {
+            return true;
+        }
+        match &self.content {
+            TmpFsContent::File(_) => false,
+            TmpFsContent::Directory(children) => {
+                children.read().values().any(|child| child.contains(ino))
+            }
+        }
+    }
+    fn resolve(&self, target: &Arc<dyn Inode>) -> Result<Arc<TmpFsInode>, FsError> {
+        let ino = target.metadata().ino;
+        let directory = self
+            .shared
+            .directories
+            .lock()
+            .get(&ino)
+            .and_then(Weak::upgrade)
+            .ok_or(FsError::NotSupported)?;
+        ensure!(
+            core::ptr::eq(
+                Arc::as_ptr(&directory) as *const u8,
+                Arc::as_ptr(target) as *const u8
+            ),
+            FsError::NotSupported
+        );
+        Ok(directory)
+    }
+}
+
+impl Drop for TmpFsInode {
+    fn drop(&mut self) {
+        if let TmpFsContent::Directory(_) = self.content {
+            self.shared.directories.lock().remove(&self.ino);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Inode for TmpFsInode {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            ino: self.ino,
+            typa: self.typa(),
+            size: match &self.content {
+                TmpFsContent::File(file) => file.read().size,
+                TmpFsContent::Directory(_) => 0,
+            },
+        }
+    }
+    async fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        let file = self.file()?.read();
+        if offset >= file.size {
+            return Ok(0);
+        }
+        let offset = offset as usize;
+        let len = usize::min(buffer.len(), file.size as usize - offset);
+        let m = config::TMPFS_PAGE_LAYOUT.size();
+        let mut ptr = offset;
+        while ptr < offset + len {
+            let r = usize::min((ptr | (m - 1)) + 1, offset + len);
+            let buffer = &mut buffer[ptr - offset..r - offset];
+            match file.pages.get(&(ptr / m)) {
+                Some(page) => page.read(ptr % m, buffer),
+                None => buffer.fill(0),
+            }
+            ptr = r;
+        }
+        Ok(len)
+    }
+    async fn write_at(&self, offset: u64, buffer: &[u8]) -> Result<usize, FsError> {
+        let mut file = self.file()?.write();
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+        let end = offset
+            .checked_add(buffer.len() as u64)
+            .ok_or(FsError::NoSpace)?;
+        ensure!(end <= config::TMPFS_FILE_MAX, FsError::NoSpace);
+        let offset = offset as usize;
+        let m = config::TMPFS_PAGE_LAYOUT.size();
+        file.populate(offset / m..(end as usize).div_ceil(m))?;
+        if offset as u64 > file.size {
+            file.zero(file.size as usize..offset);
+        }
+        if end > file.size {
+            file.size = end;
+        }
+        let mut ptr = offset;
+        while ptr < offset + buffer.len() {
+            let r = usize::min((ptr | (m - 1)) + 1, offset + buffer.len());
+            file.pages[&(ptr / m)].write(ptr % m, &buffer[ptr - offset..r - offset]);
+            ptr = r;
+        }
+        Ok(buffer.len())
+    }
+    async fn truncate(&self, size: u64) -> Result<(), FsError> {
+        self.file()?.write().resize(size)
+    }
+    fn mapping(&self, offset: u64, size: usize) -> Result<Arc<dyn MapUser>, FsError> {
+        let m = config::TMPFS_PAGE_LAYOUT.size();
+        ensure!(offset % m as u64 == 0, FsError::NotSupported);
+        let layout = size
+            .checked_next_multiple_of(m)
+            .and_then(|size| MapLayout::new(size, m))
+            .ok_or(FsError::NotSupported)?;
+        let end = offset
+            .checked_add(layout.size() as u64)
+            .ok_or(FsError::NoSpace)?;
+        ensure!(end <= config::TMPFS_FILE_MAX, FsError::NoSpace);
+        let pages = offset as usize / m..end as usize / m;
+        let mut file = self.file()?.write();
+        file.populate(pages.clone())?;
+        Ok(Arc::new(TmpFsMapping {
+            pages: file
+                .pages
+                .range(pages)
+                .map(|(_, page)| page.clone())
+                .collect(),
+            layout,
+        }))
+    }
+    async fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FsError> {
+        let children = self.children()?.read();
+        let node = children.get(name).ok_or(FsError::NotFound)?;
+        Ok(node.clone())
+    }
+    async fn readdir(&self) -> Result<Vec<DirEntry>, FsError> {
+        Ok(self
+            .children()?
+            .read()
+            .iter()
+            .map(|(name, node)| DirEntry {
+                name: name.clone(),
+                ino: node.ino,
+                typa: node.typa(),
+            })
+            .collect())
+    }
+    async fn create(&self, name: &str, typa: InodeType) -> Result<Arc<dyn Inode>, FsError> {
+        ensure!(!name.is_empty() && !name.contains('/'), FsError::BadPath);
+        let mut children = self.children()?.write();
+        ensure!(!children.contains_key(name), FsError::AlreadyExists);
+        let node = TmpFsInode::new(&self.shared, typa);
+        children.insert(name.to_string(), node.clone());
+        Ok(node)
+    }
+    async fn unlink(&self, name: &str) -> Result<(), FsError> {
+        let mut children = self.children()?.write();
+        let node = children.get(name).ok_or(FsError::NotFound)?;
+        ensure!(node.is_empty(), FsError::NotEmpty);
+        children.remove(name);
+        Ok(())
+    }
+    async fn rename(&self, name: &str, target: &Arc<dyn Inode>, new: &str) -> Result<(), FsError> {
+        ensure!(!new.is_empty() && !new.contains('/'), FsError::BadPath);
+        let target = self.resolve(target)?;
+        let node = self
+            .children()?
+            .read()
+            .get(name)
+            .cloned()
+            .ok_or(FsError::NotFound)?;
+        if node.typa() == InodeType::Directory {
+            ensure!(!node.contains(target.ino), FsError::BadPath);
+        }
+        if self.ino == target.ino {
+            let mut children = self.children()?.write();
+            return replace(&mut children, name, None, new, &node);
+        }
+        let (mut source, mut destination) = if self.ino < target.ino {
+            let source = self.children()?.write();
+            (source, target.children()?.write())
+        } else {
+            let destination = target.children()?.write();
+            (self.children()?.write(), destination)
+        };
+        replace(&mut source, name, Some(&mut destination), new, &node)
+    }
+}
+
+fn replace(
+    source: &mut BTreeMap<String, Arc<TmpFsInode>>,
+    name: &str,
+    destination: Option<&mut BTreeMap<String, Arc<TmpFsInode>>>,
+    new: &str,
+    node: &Arc<TmpFsInode>,
+) -> Result<(), FsError> {
+    ensure!(
+        source.get(name).map_or(false, |x| Arc::ptr_eq(x, node)),
+        FsError::NotFound
+    );
+    let destination = match destination {
+        Some(destination) => destination,
+        None => &mut *source,
+    };
+    if let Some(old) = destination.get(new) {
+        if Arc::ptr_eq(old, node) {
+            return Ok(());
+        }
+        match (node.typa(), old.typa()) {
+            (InodeType::File, InodeType::Directory) => return Err(FsError::IsADirectory),
+            (InodeType::Directory, InodeType::File) => return Err(FsError::NotADirectory),
+            _ => ensure!(old.is_empty(), FsError::NotEmpty),
+        }
+    }
+    destination.insert(new.to_string(), node.clone());
+    source.remove(name);
+    Ok(())
+}
+
+impl Map for TmpFsMapping {
+    fn layout(&self) -> MapLayout {
+        self.layout
+    }
+}
+
+impl MapRead for TmpFsMapping {
+    unsafe fn read_unchecked(&self, offset: usize, buffer: &mut [u8]) {
+        let m = self.layout.align();
+        let mut ptr = offset;
+        while ptr < offset + buffer.len() {
+            let r = usize::min((ptr | (m - 1)) + 1, offset + buffer.len());
+            self.pages[ptr / m]
+                .read_unchecked(ptr & (m - 1), &mut buffer[ptr - offset..r - offset]);
+            ptr = r;
+        }
+    }
+}
+
+impl MapWrite for TmpFsMapping {
+    unsafe fn write_unchecked(&self, offset: usize, buffer: &[u8]) {
+        let m = self.layout.align();
+        let mut ptr = offset;
+        while ptr < offset + buffer.len() {
+            let r = usize::min((ptr | (m - 1)) + 1, offset + buffer.len());
+            self.pages[ptr / m].write_unchecked(ptr & (m - 1), &buffer[ptr - offset..r - offset]);
+            ptr = r;
+        }
+    }
+}
+
+impl MapIndex for TmpFsMapping {
+    unsafe fn index_unchecked(&self, i: usize) -> PAddr {
+        self.pages[i].index_unchecked(0)
+    }
+}
+
+impl MapUser for TmpFsMapping {}
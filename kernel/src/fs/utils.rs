@@ -0,0 +1,19 @@
+pub fn u16_at(buffer: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(buffer[offset..offset + 2].try_into().unwrap())
+}
+
+pub fn u32_at(buffer: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buffer[offset..offset + 4].try_into().unwrap())
+}
+
+pub fn u64_at(buffer: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buffer[offset..offset + 8].try_into().unwrap())
+}
+
+pub fn set_u16_at(buffer: &mut [u8], offset: usize, value: u16) {
+    buffer[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+pub fn set_u32_at(buffer: &mut [u8], offset: usize, value: u32) {
+    buffer[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
@@ -0,0 +1,148 @@
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use drivers::block::BlockDevice;
+use spin::RwLock;
+
+static MOUNTS: RwLock<BTreeMap<String, Arc<dyn FileSystem>>> = RwLock::new(BTreeMap::new());
+
+pub fn normalize(path: &str) -> Result<Vec<&str>, FsError> {
+    ensure!(path.starts_with('/'), FsError::BadPath);
+    let mut components = Vec::new();
+    for name in path.split('/') {
+        match name {
+            "" | "." => (),
+            ".." => {
+                components.pop();
+            }
+            name => components.push(name),
+        }
+    }
+    Ok(components)
+}
+
+fn join(components: &[&str]) -> String {
+    let mut path = String::new();
+    for name in components {
+        path.push('/');
+        path.push_str(name);
+    }
+    if path.is_empty() {
+        path.push('/');
+    }
+    path
+}
+
+pub fn mount(path: &str, fs: Arc<dyn FileSystem>) -> Result<(), FsError> {
+    let key = join(&normalize(path)?);
+    let mut mounts = MOUNTS.write();
+    ensure!(!mounts.contains_key(&key), FsError::Busy);
+    info!("mount a file system at {}", key);
+    mounts.insert(key, fs);
+    Ok(())
+}
+
+pub async fn umount(path: &str) -> Result<(), FsError> {
+    let key = join(&normalize(path)?);
+    let fs = MOUNTS.write().remove(&key).ok_or(FsError::NotFound)?;
+    fs.sync().await
+}
+
+pub fn mounts() -> Vec<(String, Arc<dyn FileSystem>)> {
+    MOUNTS
+        .read()
+        .iter()
+        .map(|(path, fs)| (path.clone(), fs.clone()))
+        .collect()
+}
+
+pub async fn lookup(path: &str) -> Result<Arc<dyn Inode>, FsError> {
+    let components = normalize(path)?;
+    let mounts = MOUNTS.read().clone();
+    let mut current = mounts.get("/").ok_or(FsError::NotFound)?.root();
+    for (i, name) in components.iter().enumerate() {
+        current = match mounts.get(&join(&components[..=i])) {
+            Some(fs) => fs.root(),
+            None => current.lookup(name).await?,
+        };
+    }
+    Ok(current)
+}
+
+pub async fn lookup_parent(path: &str) -> Result<(Arc<dyn Inode>, String), FsError> {
+    let mut components = normalize(path)?;
+    let name = components.pop().ok_or(FsError::BadPath)?;
+    let parent = lookup(&join(&components)).await?;
+    Ok((parent, name.to_string()))
+}
+
+pub async fn create(path: &str, typa: InodeType) -> Result<Arc<dyn Inode>, FsError> {
+    let (parent, name) = lookup_parent(path).await?;
+    parent.create(&name, typa).await
+}
+
+pub async fn unlink(path: &str) -> Result<(), FsError> {
+    let (parent, name) = lookup_parent(path).await?;
+    parent.unlink(&name).await
+}
+
+pub async fn rename(old: &str, new: &str) -> Result<(), FsError> {
+    let (source, name) = lookup_parent(old).await?;
+    let (target, new) = lookup_parent(new).await?;
+    source.rename(&name, &target, &new).await
+}
+
+pub async fn truncate(inode: &Arc<dyn Inode>, size: u64) -> Result<(), FsError> {
+    fs::cache::cache().file(inode).truncate(size).await
+}
+
+pub async fn read(path: &str) -> Result<Vec<u8>, FsError> {
+    let inode = lookup(path).await?;
+    let metadata = inode.metadata();
+    ensure!(metadata.typa == InodeType::File, FsError::IsADirectory);
+    let mut buffer = vec![0u8; metadata.size as usize];
+    let mut offset = 0;
+    while offset < buffer.len() {
+        let len = inode.read_at(offset as u64, &mut buffer[offset..]).await?;
+        if len == 0 {
+            break;
+        }
+        offset += len;
+    }
+    buffer.truncate(offset);
+    Ok(buffer)
+}
+
+pub async fn sync() -> Result<(), FsError> {
+    fs::cache::cache().sync().await?;
+    for (_, fs) in mounts() {
+        fs.sync().await?;
+    }
+    Ok(())
+}
+
+pub async fn probe(device: Arc<dyn BlockDevice>) -> Option<Arc<dyn FileSystem>> {
+    if let Ok(fat32) = fs::fat32::Fat32::mount(device.clone()).await {
+        return Some(fat32);
+    }
+    if let Ok(ext2) = fs::ext2::Ext2::mount(device).await {
+        return Some(ext2);
+    }
+    None
+}
+
+pub fn init_global() {
+    mount("/", fs::memfs::memfs()).unwrap();
+    fs::memfs::memfs().insert("/tmp", None).unwrap();
+    mount("/tmp", fs::tmpfs::TmpFs::new()).unwrap();
+    for (name, device) in drivers::block::devices() {
+        if let Some(filesystem) = base::future::block_on(probe(device)) {
+            let path = format!("/mnt/{}", name);
+            if let Err(e) = fs::memfs::memfs().insert(&path, None) {
+                warn!("failed to create {}, skip mounting, reason = {:?}", path, e);
+                continue;
+            }
+            mount(&path, filesystem).unwrap();
+        }
+    }
+}
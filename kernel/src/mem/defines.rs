@@ -347,6 +347,13 @@ impl Permission {
     pub const fn as_u8(self) -> u8 {
         self.read as u8 | (self.write as u8) << 1 | (self.execute as u8) << 2
     }
+    pub const fn union(self, other: Self) -> Self {
+        Self::new(
+            self.read || other.read,
+            self.write || other.write,
+            self.execute || other.execute,
+        )
+    }
 }
 
 pub trait Map {
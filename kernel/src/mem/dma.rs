@@ -17,6 +17,12 @@ fn dma_dealloc(addr: usize, size: usize, align: usize) {
     }
 }
 
+pub fn alloc_zeroed_slice(len: usize) -> Box<[u8], DmaAllocator> {
+    let mut vec = Vec::with_capacity_in(len, DmaAllocator);
+    vec.resize(len, 0u8);
+    vec.into_boxed_slice()
+}
+
 // S <= 4096, T = 4096 / S
 struct LinkedList<const S: usize, const T: usize> {
     map: BTreeMap<usize, (u8, [u8; T])>,
@@ -95,8 +101,8 @@ unsafe impl Allocator for DmaAllocator {
             1..=1024 => L6.lock().alloc().cast(),
             1..=2048 => L7.lock().alloc().cast(),
             _ => {
-                let size = core::cmp::max(4096, size);
                 let align = core::cmp::max(4096, align);
+                let size = size.next_multiple_of(align);
                 let addr = dma_alloc(size, align);
                 NonNull::new(addr as *mut ()).unwrap()
             }
@@ -121,8 +127,8 @@ unsafe impl Allocator for DmaAllocator {
             1..=1024 => L6.lock().dealloc(addr),
             1..=2048 => L7.lock().dealloc(addr),
             _ => {
-                let size = core::cmp::max(4096, size);
                 let align = core::cmp::max(4096, align);
+                let size = size.next_multiple_of(align);
                 dma_dealloc(addr, size, align);
             }
         }
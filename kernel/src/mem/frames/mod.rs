@@ -1,6 +1,7 @@
 mod buddy;
 
 use crate::prelude::*;
+use arrayvec::ArrayVec;
 use base::cell::SingletonCell;
 use buddy::Buddy;
 use core::alloc::Layout;
@@ -16,8 +17,11 @@ partially!(buddy::BuddyError, FramesAllocError; OutOfBounds => OutOfMemory);
 
 struct Mod {
     buddy: Mutex<Buddy<'static>>,
+    zeroed: Mutex<ArrayVec<PAddr, { config::FRAMES_ZEROED_POOL }>>,
 }
 
+const PAGE: MapLayout = MapLayout::new(4096, 4096).unwrap();
+
 static MOD: SingletonCell<Mod> = SingletonCell::new();
 
 pub unsafe fn init_global() {
@@ -29,9 +33,18 @@ pub unsafe fn init_global() {
     let buddy_start = segment.start().to_usize().div_ceil(4096);
     let buddy_end = segment.end().map(|x| x.to_usize() >> 12);
     let buddy_segment = Segment::new(buddy_start, buddy_end).unwrap();
-    let buddy = Buddy::new(buddy_segment, buffer_slice).unwrap();
+    let mut buddy = Buddy::new(buddy_segment, buffer_slice).unwrap();
+    if let Some(initrd) = memory.initrd {
+        let start = usize::max(initrd.start().to_usize() >> 12, buddy_start);
+        let end = initrd.wrapping_end().to_usize().div_ceil(4096);
+        let end = buddy_end.map_or(end, |buddy_end| usize::min(end, buddy_end));
+        if start < end {
+            buddy.set(by_points(start, end).unwrap(), true).unwrap();
+        }
+    }
     let allocator = Mod {
         buddy: Mutex::new(buddy),
+        zeroed: Mutex::new(ArrayVec::new()),
     };
     MOD.initialize(allocator);
 }
@@ -45,11 +58,61 @@ pub fn alloc(layout: MapLayout) -> Result<PAddr, FramesAllocError> {
     if layout.align() < 4096 {
         return Err(UndersizeAlign);
     }
-    let mut buddy = MOD.buddy.lock();
-    let paddr = buddy.alloc(layout.size() >> 12).out::<FramesAllocError>()?;
+    let pages = layout.size() >> 12;
+    let mut result = MOD.buddy.lock().alloc(pages);
+    if result.is_err() && drain() {
+        result = MOD.buddy.lock().alloc(pages);
+    }
+    let paddr = result.out::<FramesAllocError>()?;
     Ok(PAddr::new(paddr << 12))
 }
 
+fn drain() -> bool {
+    let zeroed = core::mem::take(&mut *MOD.zeroed.lock());
+    let mut buddy = MOD.buddy.lock();
+    for paddr in zeroed.iter() {
+        buddy.dealloc(paddr.to_usize() >> 12, 1).unwrap();
+    }
+    !zeroed.is_empty()
+}
+
+pub fn alloc_zeroed(layout: MapLayout) -> Result<PAddr, FramesAllocError> {
+    if layout == PAGE {
+        if let Some(paddr) = MOD.zeroed.lock().pop() {
+            return Ok(paddr);
+        }
+    }
+    let paddr = alloc(layout)?;
+    if layout.size() != 0 {
+        unsafe {
+            core::ptr::write_bytes(paddr.to_mut(), 0, layout.size());
+        }
+    }
+    Ok(paddr)
+}
+
+pub fn refill() -> bool {
+    for _ in 0..config::FRAMES_ZEROED_BATCH {
+        if MOD.zeroed.lock().is_full() {
+            return false;
+        }
+        let paddr = match MOD.buddy.lock().alloc(1) {
+            Ok(paddr) => PAddr::new(paddr << 12),
+            Err(_) => return false,
+        };
+        unsafe {
+            core::ptr::write_bytes(paddr.to_mut(), 0, PAGE.size());
+        }
+        if let Err(e) = MOD.zeroed.lock().try_push(paddr) {
+            unsafe {
+                dealloc(e.element(), PAGE);
+            }
+            return false;
+        }
+    }
+    true
+}
+
 pub unsafe fn dealloc(paddr: PAddr, layout: MapLayout) {
     if layout.size() == 0 {
         assert_eq!(paddr, PAddr::new(layout.align()));
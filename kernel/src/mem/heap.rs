@@ -26,7 +26,7 @@ fn def_map(ptr: usize) {
 
 fn def_unmap(ptr: usize) {
     let layout = MapLayout::new(4096, 4096).unwrap();
-    let paddr = VMM.page_table.unmap(VAddr::new(ptr), 4096).unwrap();
+    let (paddr, _) = VMM.page_table.unmap(VAddr::new(ptr), 4096).unwrap();
     unsafe {
         mem::frames::dealloc(paddr, layout);
         core::arch::riscv64::sfence_vma(ptr, 0);
@@ -94,7 +94,7 @@ fn dfs_set(height: u8, mut u: &mut Node, addr: usize, query_height: u8, f: impl
     }
 }
 
-fn dfs_find(height: u8, mut u: &Node, query_height: u8) -> Option<usize> {
+fn dfs_find(height: u8, mut u: &Node, query_height: u8, seed: Option<usize>) -> Option<usize> {
     fn continuous_of(height: u8, u: &Node) -> usize {
         assert!(height <= (usize::BITS - 1) as u8);
         match u {
@@ -110,10 +110,13 @@ fn dfs_find(height: u8, mut u: &Node, query_height: u8) -> Option<usize> {
     for i in (query_height + 1..=height).rev() {
         if let Err(color) = u {
             assert!(!*color);
-            return Some(addr);
+            let mask = ((1usize << i) - 1) & !((1usize << query_height) - 1);
+            return Some(addr | seed.map_or(0, |seed| seed & mask));
         }
         let raw = u.as_ref().unwrap();
-        if continuous_of(i, &raw.right) >= 1usize << query_height {
+        let left = continuous_of(i - 1, &raw.left) >= 1usize << query_height;
+        let right = continuous_of(i - 1, &raw.right) >= 1usize << query_height;
+        if right && (!left || seed.map_or(true, |seed| seed & (1 << (i - 1)) != 0)) {
             u = &raw.right;
             addr |= 1 << (i - 1);
         } else {
@@ -162,13 +165,30 @@ impl Buddy {
         }
         let h = size.checked_next_power_of_two().ok_or(OutOfBounds)?;
         for (xaddr, xheight, xu) in self.list.iter() {
-            if let Some(xpos) = dfs_find(*xheight, xu, h.log2() as u8) {
+            if let Some(xpos) = dfs_find(*xheight, xu, h.log2() as u8, None) {
                 let addr = *xaddr + xpos;
                 return Ok(addr);
             }
         }
         Err(OutOfBounds)
     }
+    pub fn find_random(&self, size: usize, seed: u64) -> Result<usize, BuddyError> {
+        use BuddyError::*;
+        if size == 0 {
+            return Err(ZeroSize);
+        }
+        let h = size.checked_next_power_of_two().ok_or(OutOfBounds)?;
+        let candidates: ArrayVec<usize, { usize::BITS as usize * 2 }> = self
+            .list
+            .iter()
+            .filter_map(|(xaddr, xheight, xu)| {
+                Some(*xaddr + dfs_find(*xheight, xu, h.log2() as u8, Some(seed as usize))?)
+            })
+            .collect();
+        ensure!(!candidates.is_empty(), OutOfBounds);
+        let choice = (seed.rotate_left(32) % candidates.len() as u64) as usize;
+        Ok(candidates[choice])
+    }
     #[allow(dead_code)]
     pub fn get(&mut self, segment: Segment<usize>) -> Result<Option<bool>, BuddyError> {
         use BuddyError::*;
@@ -262,6 +282,20 @@ fn saves() {
     }
 }
 
+#[cfg(test)]
+#[test_case]
+fn find_random() {
+    let mut s = Buddy::new(by_points(0, 1 << 20).unwrap()).unwrap();
+    s.set(by_points(0, 1 << 19).unwrap(), true).unwrap();
+    for seed in [0, 1, 0x9e3779b97f4a7c15, u64::MAX] {
+        let addr = s.find_random(4096, seed).unwrap();
+        assert!(addr >= 1 << 19 && addr < 1 << 20 && addr % 4096 == 0);
+        s.set(by_size(addr, 4096).unwrap(), true).unwrap();
+        s.set(by_size(addr, 4096).unwrap(), false).unwrap();
+    }
+    assert!(s.find_random(1 << 20, 0).is_err());
+}
+
 #[cfg(test)]
 #[test_case]
 fn set() {
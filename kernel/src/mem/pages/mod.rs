@@ -119,4 +119,14 @@ impl<'a, T> PagesGuard<'a, T> {
         let vaddr = VAddr::new(vaddr);
         Ok(by_size(vaddr, layout.size()).unwrap())
     }
+    pub fn find_random(
+        &self,
+        layout: MapLayout,
+        seed: u64,
+    ) -> Result<Segment<VAddr>, PagesFindError> {
+        let inner = &*self.g;
+        let vaddr = inner.buddy.find_random(layout.size(), seed)?;
+        let vaddr = VAddr::new(vaddr);
+        Ok(by_size(vaddr, layout.size()).unwrap())
+    }
 }
@@ -1,6 +1,7 @@
 mod backtrace;
 mod config;
 mod paging;
+mod plic;
 mod rt;
 mod sbi;
 mod startup;
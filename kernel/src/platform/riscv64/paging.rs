@@ -1,5 +1,6 @@
 use crate::prelude::*;
 use core::fmt::Debug;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use mem::frames::FramesBox;
 use rt::paging::*;
 use spin::Mutex;
@@ -34,6 +35,9 @@ impl PagingEntry {
     const fn next(&self) -> bool {
         (self.0 >> 1) & 7 == 0
     }
+    const fn dirty(&self) -> bool {
+        (self.0 >> 7) & 1 != 0
+    }
     const fn addr(&self) -> PAddr {
         // 52 bit physical address
         PAddr::new(((self.0 >> 10) & 0xFFFFFFFFFFF) << 12)
@@ -61,6 +65,22 @@ unsafe fn find(root: &FramesBox<PagingFrame>, vpns: &[usize]) -> *mut PagingEntr
     child
 }
 
+unsafe fn lookup(root: &FramesBox<PagingFrame>, vpns: &[usize]) -> Option<*mut PagingEntry> {
+    assert!(!vpns.is_empty());
+    let mut child = &mut (*root.get())[vpns[0]];
+    for idx in vpns.iter().copied().skip(1) {
+        if !child.valid() || !child.next() {
+            return None;
+        }
+        child = &mut (*(child.addr().to_mut() as *mut PagingFrame))[idx];
+    }
+    Some(child)
+}
+
+unsafe fn clear(pte: *mut PagingEntry, bits: usize) -> PagingEntry {
+    PagingEntry((*(pte as *const AtomicUsize)).fetch_and(!bits, Ordering::SeqCst))
+}
+
 unsafe fn alloc(root: &FramesBox<PagingFrame>, vpns: &[usize]) -> *mut PagingEntry {
     assert!(!vpns.is_empty());
     let mut child = &mut (*root.get())[vpns[0]];
@@ -176,7 +196,7 @@ impl RawPagingInner {
         }
         Err(AlignNotSupported)
     }
-    fn unmap(&mut self, vaddr: VAddr, align: usize) -> Result<PAddr, PagingUnmapError> {
+    fn unmap(&mut self, vaddr: VAddr, align: usize) -> Result<(PAddr, bool), PagingUnmapError> {
         use PagingUnmapError::*;
         if vaddr.to_usize() & (align - 1) != 0 || resolve(vaddr).is_none() {
             return Err(InvalidVAddr);
@@ -187,38 +207,58 @@ impl RawPagingInner {
             unsafe {
                 let pte = find(&self.root, &[p3, p2, p1]);
                 assert!((*pte).valid(), "Overlapping");
-                let paddr = (*pte).addr();
-                pte.write_volatile(PagingEntry::new());
+                let old = clear(pte, !0);
                 maintain(&self.root, &[p3, p2]);
-                return Ok(paddr);
+                return Ok((old.addr(), old.dirty()));
             }
         }
         if align == 2 * 1024 * 1024 {
             unsafe {
                 let pte = find(&self.root, &[p3, p2]);
                 assert!((*pte).valid(), "Overlapping");
-                let paddr = (*pte).addr();
-                pte.write_volatile(PagingEntry::new());
+                let old = clear(pte, !0);
                 maintain(&self.root, &[p3]);
-                return Ok(paddr);
+                return Ok((old.addr(), old.dirty()));
             }
         }
         if align == 1024 * 1024 * 1024 {
             unsafe {
                 let pte = find(&self.root, &[p3]);
                 assert!((*pte).valid(), "Overlapping");
-                let paddr = (*pte).addr();
-                pte.write_volatile(PagingEntry::new());
+                let old = clear(pte, !0);
                 maintain(&self.root, &[]);
-                return Ok(paddr);
+                return Ok((old.addr(), old.dirty()));
             }
         }
         Err(AlignNotSupported)
     }
+    fn clean(&mut self, vaddr: VAddr, paddr: PAddr, align: usize) -> bool {
+        let Some(([p3, p2, p1], _)) = resolve(vaddr) else {
+            return false;
+        };
+        let vpns: &[usize] = if align == 4 * 1024 {
+            &[p3, p2, p1]
+        } else if align == 2 * 1024 * 1024 {
+            &[p3, p2]
+        } else if align == 1024 * 1024 * 1024 {
+            &[p3]
+        } else {
+            return false;
+        };
+        unsafe {
+            match lookup(&self.root, vpns) {
+                Some(pte) if (*pte).valid() && !(*pte).next() && (*pte).addr() == paddr => {
+                    clear(pte, 1 << 7).dirty()
+                }
+                _ => false,
+            }
+        }
+    }
 }
 
 pub struct RawPaging {
     inner: Mutex<RawPagingInner>,
+    active: AtomicUsize,
 }
 
 impl RawPaging {
@@ -226,12 +266,26 @@ impl RawPaging {
         let mut inner = self.inner.lock();
         inner.token()
     }
+    pub(in crate::platform) fn enter(&self, id: usize) {
+        self.active.fetch_or(1 << id, Ordering::SeqCst);
+    }
+    pub(in crate::platform) fn leave(&self, id: usize) {
+        self.active.fetch_and(!(1 << id), Ordering::SeqCst);
+    }
+    fn shootdown(&self, vaddr: VAddr, align: usize) {
+        core::sync::atomic::fence(Ordering::SeqCst);
+        let mask = self.active.load(Ordering::SeqCst);
+        if mask != 0 {
+            super::sbi::remote_sfence_vma(&mask, vaddr.to_usize(), align);
+        }
+    }
 }
 
 impl Paging for RawPaging {
     fn new() -> Self {
         Self {
             inner: Mutex::new(RawPagingInner::new()),
+            active: AtomicUsize::new(0),
         }
     }
     fn map(
@@ -246,9 +300,17 @@ impl Paging for RawPaging {
         let mut inner = self.inner.lock();
         inner.map(vaddr, paddr, align, permission, user, global)
     }
-    fn unmap(&self, vaddr: VAddr, align: usize) -> Result<PAddr, PagingUnmapError> {
-        let mut inner = self.inner.lock();
-        inner.unmap(vaddr, align)
+    fn unmap(&self, vaddr: VAddr, align: usize) -> Result<(PAddr, bool), PagingUnmapError> {
+        let unmapped = self.inner.lock().unmap(vaddr, align)?;
+        self.shootdown(vaddr, align);
+        Ok(unmapped)
+    }
+    fn clean(&self, vaddr: VAddr, paddr: PAddr, align: usize) -> bool {
+        let dirty = self.inner.lock().clean(vaddr, paddr, align);
+        if dirty {
+            self.shootdown(vaddr, align);
+        }
+        dirty
     }
 }
 
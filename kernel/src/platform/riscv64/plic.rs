@@ -0,0 +1,57 @@
+use crate::prelude::*;
+use base::cell::{SingletonCell, VolCell};
+
+pub struct Plic {
+    addr: usize,
+}
+
+static PLIC: SingletonCell<Plic> = SingletonCell::new();
+
+pub fn maybe_plic() -> Option<&'static Plic> {
+    PLIC.maybe()
+}
+
+pub fn register(addr: PAddr, _size: usize) {
+    PLIC.initialize(Plic {
+        addr: addr.to_usize(),
+    });
+}
+
+impl Plic {
+    // todo: parse `interrupts-extended` instead of assuming the layout of QEMU virt
+    fn context(hart: usize) -> usize {
+        2 * hart + 1
+    }
+    fn reg(&self, offset: usize) -> &VolCell<u32> {
+        unsafe { &*((self.addr + offset) as *const VolCell<u32>) }
+    }
+    fn priority(&self, value: usize) -> &VolCell<u32> {
+        self.reg(4 * value)
+    }
+    fn enable(&self, hart: usize, value: usize) -> &VolCell<u32> {
+        self.reg(0x2000 + 0x80 * Self::context(hart) + 4 * (value / 32))
+    }
+    fn threshold(&self, hart: usize) -> &VolCell<u32> {
+        self.reg(0x200000 + 0x1000 * Self::context(hart))
+    }
+    fn claim(&self, hart: usize) -> &VolCell<u32> {
+        self.reg(0x200004 + 0x1000 * Self::context(hart))
+    }
+    pub fn interrupt_enable(&self, value: usize) {
+        self.priority(value).write(1);
+        for &hart in rt::thread::threads().keys() {
+            let enable = self.enable(hart, value);
+            enable.write(enable.read() | 1 << (value % 32));
+            self.threshold(hart).write(0);
+        }
+    }
+    pub fn interrupt_claim(&self, hart: usize) -> Option<usize> {
+        match self.claim(hart).read() {
+            0 => None,
+            value => Some(value as usize),
+        }
+    }
+    pub fn interrupt_complete(&self, hart: usize, value: usize) {
+        self.claim(hart).write(value as u32);
+    }
+}
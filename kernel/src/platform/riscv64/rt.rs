@@ -1,5 +1,6 @@
 use super::backtrace::resolve;
 use super::paging::RawPaging;
+use super::plic::maybe_plic;
 use super::startup::{ID, TRAMPOLINE};
 use super::trap::{RawTrapping, TrapFrame};
 use crate::platform::riscv64::startup::EXTRA;
@@ -57,6 +58,24 @@ impl Platform for P {
         unreachable!()
     }
 
+    fn shutdown(code: isize) -> ! {
+        use super::sbi::*;
+        let reason = if code == 0 {
+            RESET_REASON_NONE
+        } else {
+            RESET_REASON_SYSTEM_FAILURE
+        };
+        system_reset(RESET_TYPE_SHUTDOWN, reason);
+        super::sbi::shutdown();
+        unreachable!()
+    }
+
+    fn reboot() -> ! {
+        use super::sbi::*;
+        system_reset(RESET_TYPE_COLD_REBOOT, RESET_REASON_NONE);
+        panic!("failed to reboot the system")
+    }
+
     fn write(s: &str) {
         for c in s.bytes() {
             super::sbi::console_putchar(c.into());
@@ -86,7 +105,9 @@ impl Platform for P {
         let switch = TRAMPOLINE + (_trampoline_switch.as_vaddr() - _trampoline_start.as_vaddr());
         let trapframe = EXTRA[&current().id()].trapaddr.to_usize() as *mut TrapFrame;
         (*trapframe).ctx = ctx.clone();
+        pt.enter(current().id());
         core::mem::transmute::<VAddr, extern "C" fn(usize)>(switch)(pt.token());
+        pt.leave(current().id());
         *ctx = (*trapframe).ctx.clone();
         let stval = stval::read();
         match scause::read().bits() {
@@ -134,4 +155,38 @@ impl Platform for P {
             _ => TrapUnknown,
         }
     }
+
+    fn interrupt_enable(value: usize) {
+        if let Some(plic) = maybe_plic() {
+            plic.interrupt_enable(value);
+        }
+    }
+
+    fn interrupt_claim() -> Option<usize> {
+        maybe_plic()?.interrupt_claim(current().id())
+    }
+
+    fn interrupt_complete(value: usize) {
+        if let Some(plic) = maybe_plic() {
+            plic.interrupt_complete(current().id(), value);
+        }
+    }
+
+    fn wait() {
+        unsafe {
+            riscv::asm::wfi();
+        }
+    }
+
+    fn ipi_send(id: usize) {
+        assert!(id < usize::BITS as usize);
+        let mask = 1usize << id;
+        super::sbi::send_ipi(&mask);
+    }
+
+    fn ipi_clear() {
+        unsafe {
+            riscv::register::sip::clear_ssoft();
+        }
+    }
 }
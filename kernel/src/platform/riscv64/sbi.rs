@@ -197,3 +197,17 @@ const TIMER_SET_TIMER: usize = 0;
 pub fn timer_set_timer(time: u64) -> SBIResult {
     unsafe { ecall!(TIMER_ID, TIMER_SET_TIMER, time) }
 }
+
+const SRST_ID: usize = 0x53525354;
+const SRST_SYSTEM_RESET: usize = 0;
+
+pub const RESET_TYPE_SHUTDOWN: u32 = 0;
+pub const RESET_TYPE_COLD_REBOOT: u32 = 1;
+pub const RESET_TYPE_WARM_REBOOT: u32 = 2;
+
+pub const RESET_REASON_NONE: u32 = 0;
+pub const RESET_REASON_SYSTEM_FAILURE: u32 = 1;
+
+pub fn system_reset(typa: u32, reason: u32) -> SBIResult {
+    unsafe { ecall!(SRST_ID, SRST_SYSTEM_RESET, typa as usize, reason as usize) }
+}
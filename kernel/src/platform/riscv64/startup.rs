@@ -234,8 +234,14 @@ unsafe extern "C" fn _start(cpuid: usize, opaque: *const u8) -> ! {
     core::arch::riscv64::sfence_vma_all();
     core::arch::riscv64::fence_i();
     mem::heap::init_global();
+    rt::time::init_local(Box::new(HartTime {
+        freq: EXTRA[&cpuid].frequency,
+    }));
+    rt::random::init_global();
     sched::scheduler::init_global();
     drivers::manager::init_global();
+    fs::vfs::init_global();
+    sched::scheduler::init_initproc();
     SATP.store(mem::vmm::VMM.page_table.token(), Ordering::SeqCst);
     for &id in rt::thread::threads().keys() {
         if id == cpuid {
@@ -253,6 +259,9 @@ unsafe extern "C" fn _start(cpuid: usize, opaque: *const u8) -> ! {
 #[no_mangle]
 unsafe extern "C" fn _start2(cpuid: usize) -> ! {
     ID = cpuid;
+    rt::time::init_local(Box::new(HartTime {
+        freq: EXTRA[&cpuid].frequency,
+    }));
     _start3();
 }
 
@@ -272,9 +281,6 @@ unsafe fn _start3() -> ! {
     core::arch::asm!("mv {}, tp", out(reg)(*trapframe).fault_tp);
     (*trapframe).fault_sp = FAULTSTACK.0.as_mut_ptr_range().end as usize;
     (*trapframe).switch_satp = mem::vmm::VMM.page_table.token();
-    rt::time::init_local(Box::new(HartTime {
-        freq: EXTRA[&current().id()].frequency,
-    }));
     cfg_if::cfg_if! {
         if #[cfg(test)] {
             crate::harness_main();
@@ -309,6 +315,12 @@ unsafe fn scan(src: *const u8) -> (MemoryBuilder, GlobalBuilder, ThreadsBuilder)
         let size = region.size.unwrap();
         region_builder = MemoryBuilder::new(by_size(addr, size).unwrap());
         region_builder.brk(address(&_brk_ptr));
+        if let Some(initrd) = initrd(&dt) {
+            info!("initrd is detected at {:?}", initrd);
+            if let Err(e) = region_builder.initrd(initrd) {
+                warn!("initrd is ignored, reason = {:?}", e);
+            }
+        }
         region_builder.alloc_buffer();
     }
     let mut threads_builder = ThreadsBuilder::new();
@@ -363,23 +375,37 @@ unsafe fn scan(src: *const u8) -> (MemoryBuilder, GlobalBuilder, ThreadsBuilder)
 
 fn solve(node: FdtNode) {
     if let Some(compatible) = node.compatible() {
-        let mut all = compatible.all();
-        if all.any(|s| s == "virtio,mmio") {
-            let reg = node.property("reg").unwrap().value;
-            assert!(reg.len() == 2 * core::mem::size_of::<usize>());
-            let addr =
-                usize::from_be_bytes(reg[0..core::mem::size_of::<usize>()].try_into().unwrap());
-            let size =
-                usize::from_be_bytes(reg[core::mem::size_of::<usize>()..].try_into().unwrap());
+        let all = compatible.all().collect::<Vec<_>>();
+        if all.contains(&"virtio,mmio") {
+            let (addr, size) = reg(node);
             let int = node
                 .interrupts()
                 .map(Iterator::collect)
                 .unwrap_or_else(Vec::new);
             drivers::manager::register(PAddr::new(addr), size, int);
         }
+        if all.contains(&"riscv,plic0") || all.contains(&"sifive,plic-1.0.0") {
+            let (addr, size) = reg(node);
+            super::plic::register(PAddr::new(addr), size);
+        }
     }
 }
 
+fn initrd(dt: &Fdt) -> Option<Segment<PAddr>> {
+    let chosen = dt.find_node("/chosen")?;
+    let start = chosen.property("linux,initrd-start")?.as_usize()?;
+    let end = chosen.property("linux,initrd-end")?.as_usize()?;
+    by_points(PAddr::new(start), PAddr::new(end))
+}
+
+fn reg(node: FdtNode) -> (usize, usize) {
+    let reg = node.property("reg").unwrap().value;
+    assert!(reg.len() == 2 * core::mem::size_of::<usize>());
+    let addr = usize::from_be_bytes(reg[0..core::mem::size_of::<usize>()].try_into().unwrap());
+    let size = usize::from_be_bytes(reg[core::mem::size_of::<usize>()..].try_into().unwrap());
+    (addr, size)
+}
+
 unsafe extern "C" fn _fault_handler() -> ! {
     let mut handle = rt::io::stdout().lock();
     writeln!(handle).unwrap();
@@ -1,8 +1,8 @@
 pub use crate::config;
-pub use crate::fs;
 pub use crate::rust;
 pub use crate::{base, base::either::*, base::error::*};
 pub use crate::{drivers, drivers::defines::*};
+pub use crate::{fs, fs::defines::*};
 pub use crate::{mem, mem::defines::*};
 pub use crate::{proc, proc::defines::*};
 pub use crate::{rt, rt::macros::*, rt::trap::*};
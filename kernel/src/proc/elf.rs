@@ -0,0 +1,71 @@
+use crate::prelude::*;
+use fs::utils::u64_at;
+
+pub const DT_NULL: u64 = 0;
+pub const DT_RELA: u64 = 7;
+pub const DT_RELASZ: u64 = 8;
+pub const DT_RELAENT: u64 = 9;
+pub const DT_REL: u64 = 17;
+
+pub const AT_NULL: usize = 0;
+pub const AT_PHDR: usize = 3;
+pub const AT_PHENT: usize = 4;
+pub const AT_PHNUM: usize = 5;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_BASE: usize = 7;
+pub const AT_ENTRY: usize = 9;
+pub const AT_RANDOM: usize = 25;
+
+pub const R_RISCV_NONE: u32 = 0;
+pub const R_RISCV_RELATIVE: u32 = 3;
+
+const DYNAMIC_SIZE: usize = 16;
+const RELA_SIZE: usize = 24;
+
+#[derive(Debug)]
+pub enum ElfParseError {
+    BadElf,
+    BadAbi,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rela {
+    pub offset: u64,
+    pub typa: u32,
+    pub symbol: u32,
+    pub addend: i64,
+}
+
+pub fn relocations<'a>(
+    dynamic: &[u8],
+    translate: impl Fn(u64, u64) -> Option<&'a [u8]>,
+) -> Result<Vec<Rela>, ElfParseError> {
+    use ElfParseError::*;
+    let dynamic = dynamic
+        .chunks_exact(DYNAMIC_SIZE)
+        .map(|entry| (u64_at(entry, 0), u64_at(entry, 8)))
+        .take_while(|&(tag, _)| tag != DT_NULL)
+        .collect::<Vec<_>>();
+    let find = |tag| dynamic.iter().find(|x| x.0 == tag).map(|x| x.1);
+    ensure!(find(DT_REL).is_none(), BadAbi);
+    let (addr, size) = match (find(DT_RELA), find(DT_RELASZ)) {
+        (Some(addr), Some(size)) => (addr, size),
+        (None, _) => return Ok(Vec::new()),
+        _ => return Err(BadElf),
+    };
+    let entry_size = find(DT_RELAENT).unwrap_or(RELA_SIZE as u64) as usize;
+    ensure!(entry_size >= RELA_SIZE, BadElf);
+    let table = translate(addr, size).ok_or(BadElf)?;
+    Ok(table
+        .chunks_exact(entry_size)
+        .map(|entry| {
+            let info = u64_at(entry, 8);
+            Rela {
+                offset: u64_at(entry, 0),
+                typa: info as u32,
+                symbol: (info >> 32) as u32,
+                addend: u64_at(entry, 16) as i64,
+            }
+        })
+        .collect())
+}
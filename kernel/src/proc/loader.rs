@@ -1,4 +1,7 @@
 use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use fs::mapping::FileMapping;
+use proc::elf::*;
 use proc::vmm::UserSpace;
 use proc::vmm::*;
 use user::objects::memory::Memory;
@@ -11,10 +14,12 @@ use zelf::{Class, Data, Version};
 #[derive(Debug)]
 pub enum LoadError {
     NotFound,
+    BadFile,
     BadElf,
     BadAbi,
     BadPlatform,
     OutOfMemory,
+    OutOfVirtualMemory,
     NotSupported,
     BadAddress,
     SegmentOfUndersizeAlign,
@@ -25,6 +30,15 @@ pub enum LoadError {
     PermissionNotSupported,
 }
 
+impl From<FsError> for LoadError {
+    fn from(e: FsError) -> Self {
+        match e {
+            FsError::NotFound | FsError::NotADirectory | FsError::BadPath => Self::NotFound,
+            _ => Self::BadFile,
+        }
+    }
+}
+
 impl From<ParseProgramsError> for LoadError {
     fn from(_: ParseProgramsError) -> Self {
         Self::BadElf
@@ -37,6 +51,8 @@ impl From<ParseProgramError> for LoadError {
     }
 }
 
+fully!(ElfParseError, LoadError; BadElf, BadAbi);
+
 fully!(MemoryCreateError, LoadError;
     OutOfMemory => OutOfMemory,
     UndersizeAlign => SegmentOfUndersizeAlign
@@ -51,10 +67,17 @@ fully!(AreaMapError, LoadError;
     PermissionNotSupported => PermissionNotSupported
 );
 
+fully!(AreaFindCreateError, LoadError;
+    ZeroSize => SegmentOfZeroSize,
+    OutOfRange => NotSupported,
+    OutOfVirtualMemory => OutOfVirtualMemory
+);
+
 pub struct Image {
     pub space: Arc<UserSpace>,
     pub pc: VAddr,
     pub tls: Option<ImageTls>,
+    pub auxv: Vec<(usize, usize)>,
 }
 
 pub struct ImageTls {
@@ -62,60 +85,333 @@ pub struct ImageTls {
     pub content: Box<[u8]>,
 }
 
-pub fn load(name: &str) -> Result<Image, LoadError> {
+struct Segment<'a> {
+    typa: ProgramType,
+    permission: Permission,
+    offset: u64,
+    vaddr: u64,
+    memsz: u64,
+    align: u64,
+    content: &'a [u8],
+}
+
+impl Segment<'_> {
+    fn is_load(&self) -> bool {
+        matches!(self.typa, ProgramType::Load) && self.memsz != 0
+    }
+}
+
+fn translate<'a>(segments: &[Segment<'a>], vaddr: u64, len: u64) -> Option<&'a [u8]> {
+    segments.iter().filter(|x| x.is_load()).find_map(|segment| {
+        let start = usize::try_from(vaddr.checked_sub(segment.vaddr)?).ok()?;
+        let end = start.checked_add(usize::try_from(len).ok()?)?;
+        segment.content.get(start..end)
+    })
+}
+
+struct Run {
+    start: usize,
+    pages: usize,
+    permission: Permission,
+    shared: Option<usize>,
+}
+
+fn map_segments(
+    area: &Area,
+    inode: &Arc<dyn Inode>,
+    segments: &[Segment<'_>],
+    base: u64,
+) -> Result<(), LoadError> {
+    use LoadError::*;
+    let m = config::PAGE_CACHE_LAYOUT.size();
+    let mut loads = Vec::new();
+    for program in segments.iter().filter(|x| x.is_load()) {
+        let vaddr = base.checked_add(program.vaddr).ok_or(BadAddress)?;
+        let vaddr = usize::try_from(vaddr).map_err(|_| BadAddress)?;
+        ensure!(
+            program.offset % m as u64 == (vaddr % m) as u64,
+            SegmentOfBadLayout
+        );
+        let memsz = usize::try_from(program.memsz).map_err(|_| SegmentOfBadLayout)?;
+        let end = vaddr
+            .checked_add(memsz)
+            .and_then(|x| x.checked_next_multiple_of(m))
+            .ok_or(SegmentOfBadLayout)?;
+        loads.push((vaddr, end, program));
+    }
+    let mut plan = BTreeMap::<usize, (Permission, Vec<usize>)>::new();
+    for (i, &(vaddr, end, program)) in loads.iter().enumerate() {
+        for page in (vaddr & !(m - 1)..end).step_by(m) {
+            let (permission, sources) = plan
+                .entry(page)
+                .or_insert((Permission::new(false, false, false), Vec::new()));
+            *permission = permission.union(program.permission);
+            sources.push(i);
+        }
+    }
+    let shareable = |i: usize| {
+        let program = loads[i].2;
+        !program.permission.write && program.content.len() as u64 == program.memsz
+    };
+    let mut runs = Vec::<Run>::new();
+    for (&page, &(permission, ref sources)) in plan.iter() {
+        let shared = match sources[..] {
+            [i] if shareable(i) => Some(i),
+            _ => None,
+        };
+        match runs.last_mut() {
+            Some(run)
+                if run.start + run.pages * m == page
+                    && run.permission == permission
+                    && run.shared == shared =>
+            {
+                run.pages += 1;
+            }
+            _ => runs.push(Run {
+                start: page,
+                pages: 1,
+                permission,
+                shared,
+            }),
+        }
+    }
+    for run in runs {
+        let size = run.pages * m;
+        let mapping = run.shared.and_then(|i| {
+            let (vaddr, _, program) = loads[i];
+            let offset = program.offset + run.start as u64 - vaddr as u64;
+            match inode.mapping(offset, size) {
+                Ok(mapping) => Some(mapping),
+                Err(_) => {
+                    FileMapping::new(inode, offset, size, false).map(|x| x as Arc<dyn MapUser>)
+                }
+            }
+        });
+        match mapping {
+            Some(mapping) => {
+                area.map(VAddr::new(run.start), mapping, run.permission)?;
+            }
+            None => {
+                let layout = MapLayout::new(size, m).ok_or(SegmentOfBadLayout)?;
+                let memory = Memory::create(layout)?;
+                for &(vaddr, _, program) in loads.iter() {
+                    let content = program.content;
+                    let start = usize::max(vaddr, run.start);
+                    let end = usize::min(vaddr + content.len(), run.start + size);
+                    if start < end {
+                        memory.write(start - run.start, &content[start - vaddr..end - vaddr]);
+                    }
+                }
+                area.map(VAddr::new(run.start), memory, run.permission)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Loaded {
+    base: u64,
+    entry: u64,
+    phdr: u64,
+    phent: u16,
+    phnum: u16,
+    interpreter: Option<String>,
+    tls: Option<ImageTls>,
+}
+
+async fn load_object(space: &UserSpace, path: &str, main: bool) -> Result<Loaded, LoadError> {
     use LoadError::*;
-    let input = fs::memfs::memfs().read(name).ok_or(NotFound)?;
-    let elf = match Elf::parse(input).map_err(|_| BadElf)? {
+    let inode = fs::vfs::lookup(path).await?;
+    let input = fs::vfs::read(path).await?;
+    let elf = match Elf::parse(input.as_slice()).map_err(|_| BadElf)? {
         Elf::Little64(e) => e,
         _ => return Err(BadPlatform),
     };
     let header = elf.header();
     let ident = header.ident();
-    let mut load = Image {
-        space: UserSpace::new(),
-        pc: VAddr::new(elf.header().entry() as usize),
-        tls: None,
-    };
     ensure!(ident.class() == Class::Class64, BadPlatform);
     ensure!(ident.data() == Data::Little, BadPlatform);
     ensure!(ident.version() == Version::One, BadPlatform);
     ensure!(ident.os_abi() == 0, BadAbi);
     ensure!(ident.abi_version() == 0, BadAbi);
     ensure!(header.machine() == P::ABI_ELF_ABI, BadPlatform);
-    ensure!(header.typa() == ElfType::Exec, BadAbi);
+    let (typa, entry, phoff) = (header.typa(), header.entry(), header.phoff());
+    let (phent, phnum) = (header.phentsize(), header.phnum());
     let programs = Programs::parse(elf)?.ok_or(BadElf)?;
+    let mut segments = Vec::with_capacity(programs.num());
     for index in 0..programs.num() {
         let program = Program::parse(programs, index).unwrap()?;
         let header = program.header();
-        match header.typa() {
-            ProgramType::Load => {
-                let vaddr = VAddr::new(header.vaddr() as usize);
-                let size = header.memsz() as usize;
-                let align = header.align() as usize;
-                let layout = MapLayout::new(size, align).ok_or(SegmentOfBadLayout)?;
-                let memory = Memory::create(layout)?;
-                let permission = Permission {
-                    read: header.flags() & ProgramFlags::READ != 0.into(),
-                    write: header.flags() & ProgramFlags::WRITE != 0.into(),
-                    execute: header.flags() & ProgramFlags::EXECUTE != 0.into(),
-                };
-                load.space.root.map(vaddr, memory.clone(), permission)?;
-                memory.write(0, program.content());
-            }
+        let content = program.content();
+        ensure!(content.len() as u64 <= header.memsz(), BadElf);
+        segments.push(Segment {
+            typa: header.typa(),
+            permission: Permission {
+                read: header.flags() & ProgramFlags::READ != 0.into(),
+                write: header.flags() & ProgramFlags::WRITE != 0.into(),
+                execute: header.flags() & ProgramFlags::EXECUTE != 0.into(),
+            },
+            offset: header.offset(),
+            vaddr: header.vaddr(),
+            memsz: header.memsz(),
+            align: header.align(),
+            content,
+        });
+    }
+    let loads = || segments.iter().filter(|x| x.is_load());
+    let (area, base) = match typa {
+        ElfType::Exec if main => (space.root.clone(), 0),
+        ElfType::Dyn => {
+            let m = config::PAGE_CACHE_LAYOUT.size() as u64;
+            let align = loads().map(|program| program.align).fold(m, u64::max);
+            ensure!(align.is_power_of_two(), SegmentOfBadLayout);
+            let start = loads().map(|program| program.vaddr).min().ok_or(BadElf)? & !(align - 1);
+            let end = loads()
+                .map(|program| program.vaddr.checked_add(program.memsz))
+                .try_fold(0, |end, x| x.map(|x| u64::max(end, x)))
+                .ok_or(BadAddress)?;
+            let size = usize::try_from((end - start).next_multiple_of(align))
+                .map_err(|_| SegmentOfBadLayout)?;
+            let layout = MapLayout::new(size, align as usize).ok_or(SegmentOfBadLayout)?;
+            let area = space.root.find_create(layout)?;
+            let base = (area.segment.start().to_usize() as u64).wrapping_sub(start);
+            (area, base)
+        }
+        _ => return Err(BadAbi),
+    };
+    let mut tls = None;
+    let mut interpreter = None;
+    let mut phdr = None;
+    let mut dynamic = None;
+    map_segments(&area, &inode, &segments, base)?;
+    for program in segments.iter() {
+        match program.typa {
             ProgramType::Tls => {
-                if load.tls.is_some() {
-                    return Err(BadAbi);
-                }
-                let size = header.memsz() as usize;
-                let align = header.align() as usize;
+                ensure!(tls.is_none(), BadAbi);
+                let align = usize::max(program.align as usize, 1);
+                ensure!(align.is_power_of_two(), SegmentOfBadLayout);
+                let size = (program.memsz as usize).next_multiple_of(align);
                 let layout = MapLayout::new(size, align).ok_or(SegmentOfBadLayout)?;
-                load.tls = Some(ImageTls {
+                tls = Some(ImageTls {
                     layout,
-                    content: program.content().to_vec().into_boxed_slice(),
+                    content: program.content.to_vec().into_boxed_slice(),
                 });
             }
-            _ => return Err(NotSupported),
+            ProgramType::Interp => {
+                ensure!(main && interpreter.is_none(), BadAbi);
+                let content = program.content.split(|&c| c == 0).next().unwrap();
+                let path = core::str::from_utf8(content).map_err(|_| BadElf)?;
+                interpreter = Some(path.to_string());
+            }
+            ProgramType::Phdr => phdr = Some(program.vaddr),
+            ProgramType::Dynamic => dynamic = Some(program.content),
+            _ => (),
+        }
+    }
+    let phdr = phdr.or_else(|| {
+        let program = loads().find(|p| {
+            p.offset <= phoff && phoff < p.offset.saturating_add(p.content.len() as u64)
+        })?;
+        Some(program.vaddr + (phoff - program.offset))
+    });
+    if let Some(dynamic) = dynamic.filter(|_| main && interpreter.is_none()) {
+        let relocations = relocations(dynamic, |vaddr, len| translate(&segments, vaddr, len))?;
+        for rela in relocations {
+            match rela.typa {
+                R_RISCV_NONE => (),
+                R_RISCV_RELATIVE if rela.symbol == 0 => {
+                    let addr = VAddr::new(base.wrapping_add(rela.offset) as usize);
+                    let value = base.wrapping_add(rela.addend as u64);
+                    area.write(addr, &value.to_le_bytes())
+                        .map_err(|e| match e {
+                            AreaWriteError::PermissionDenied => NotSupported,
+                            AreaWriteError::OutOfRange | AreaWriteError::BadWrite => BadAddress,
+                        })?;
+                }
+                _ => return Err(NotSupported),
+            }
+        }
+    }
+    Ok(Loaded {
+        base,
+        entry: base.wrapping_add(entry),
+        phdr: phdr.map_or(0, |phdr| base.wrapping_add(phdr)),
+        phent,
+        phnum,
+        interpreter,
+        tls,
+    })
+}
+
+pub async fn load(path: &str, randomized: bool) -> Result<Image, LoadError> {
+    let space = UserSpace::new(randomized);
+    let main = load_object(&space, path, true).await?;
+    let mut auxv = vec![
+        (AT_PHDR, main.phdr as usize),
+        (AT_PHENT, main.phent as usize),
+        (AT_PHNUM, main.phnum as usize),
+        (AT_ENTRY, main.entry as usize),
+    ];
+    let pc = match &main.interpreter {
+        Some(interpreter) => {
+            let interpreter = load_object(&space, interpreter, false).await?;
+            auxv.push((AT_BASE, interpreter.base as usize));
+            interpreter.entry
+        }
+        None => {
+            auxv.push((AT_BASE, 0));
+            main.entry
         }
+    };
+    Ok(Image {
+        space,
+        pc: VAddr::new(pc as usize),
+        tls: main.tls,
+        auxv,
+    })
+}
+
+pub fn stack(argv: &[String], envp: &[String], auxv: &[(usize, usize)], top: VAddr) -> Vec<u8> {
+    let mut info = vec![0u8; 16];
+    rt::random::fill(&mut info);
+    let mut pointers = |strings: &[String]| {
+        let mut pointers = Vec::with_capacity(strings.len() + 1);
+        for string in strings {
+            pointers.push(info.len());
+            info.extend_from_slice(string.as_bytes());
+            info.push(0);
+        }
+        pointers
+    };
+    let argv = pointers(argv);
+    let envp = pointers(envp);
+    info.resize(info.len().next_multiple_of(16), 0);
+    let start = top.to_usize() - info.len();
+    let mut words = vec![argv.len()];
+    words.extend(argv.iter().map(|offset| start + offset));
+    words.push(0);
+    words.extend(envp.iter().map(|offset| start + offset));
+    words.push(0);
+    let auxv = auxv.iter().copied().chain([
+        (AT_PAGESZ, config::PAGE_CACHE_LAYOUT.size()),
+        (AT_RANDOM, start),
+        (AT_NULL, 0),
+    ]);
+    for (key, value) in auxv {
+        words.push(key);
+        words.push(value);
+    }
+    if words.len() % 2 != 0 {
+        words.push(0);
     }
-    Ok(load)
+    let mut content: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+    content.extend_from_slice(&info);
+    content
+}
+
+pub fn stack_size(argv: &[String], envp: &[String]) -> usize {
+    argv.iter()
+        .chain(envp.iter())
+        .map(|string| string.len() + 1 + core::mem::size_of::<usize>())
+        .sum()
 }
@@ -1,4 +1,5 @@
 pub mod defines;
+pub mod elf;
 pub mod handle_set;
 pub mod loader;
 pub mod process;
@@ -2,7 +2,7 @@ use crate::prelude::*;
 use crossbeam::atomic::AtomicCell;
 use proc::handle_set::HandleSet;
 use proc::loader::LoadError;
-use proc::loader::{load, ImageTls};
+use proc::loader::{load, stack, stack_size, ImageTls};
 use proc::thread::Thread;
 use proc::thread::ThreadCreateError;
 use proc::thread_set::ThreadSet;
@@ -11,6 +11,7 @@ use proc::vmm::UserSpace;
 #[derive(Debug)]
 pub enum ProcessCreateError {
     LoadError,
+    ArgumentsTooLong,
     OutOfMemory,
     OutOfVirtualMemory,
 }
@@ -56,8 +57,20 @@ impl Process {
     pub fn is_dead(&self) -> bool {
         self.status().is_dead()
     }
-    pub fn create(name: &str) -> Result<Arc<Process>, ProcessCreateError> {
-        let load = load(name)?;
+    pub async fn create(
+        path: &str,
+        argv: &[&str],
+        envp: &[&str],
+        randomized: bool,
+    ) -> Result<Arc<Process>, ProcessCreateError> {
+        use ProcessCreateError::*;
+        let argv: Vec<String> = argv.iter().map(|x| x.to_string()).collect();
+        let envp: Vec<String> = envp.iter().map(|x| x.to_string()).collect();
+        ensure!(
+            stack_size(&argv, &envp) <= config::PROCESS_ARGUMENTS_MAX,
+            ArgumentsTooLong
+        );
+        let load = load(path, randomized).await?;
         let process = Arc::new(Process {
             status: AtomicCell::new(ProcessStatus::Live),
             space: load.space,
@@ -66,19 +79,23 @@ impl Process {
             load_tls: load.tls,
         });
         let _ = process.handle_set.extend(0, Handle::new(process.clone()));
-        process.spawn(load.pc, 0).out::<ProcessCreateError>()?;
+        let auxv = load.auxv;
+        process
+            .spawn(load.pc, 0, move |top| stack(&argv, &envp, &auxv, top))
+            .out::<ProcessCreateError>()?;
         Ok(process)
     }
     pub fn spawn(
         self: &Arc<Self>,
         pc: VAddr,
         opaque: usize,
+        init: impl FnOnce(VAddr) -> Vec<u8>,
     ) -> Result<Arc<Thread>, ProcessSpawnError> {
         use ProcessSpawnError::*;
         if self.is_dead() {
             return Err(BadStatus);
         }
-        let thread = Thread::create(self, pc, opaque)?;
+        let thread = Thread::create(self, pc, opaque, init)?;
         self.thread_set.insert(thread.clone());
         Ok(thread)
     }
@@ -9,7 +9,7 @@ use proc::signal_set::SignalSet;
 use proc::vmm::AreaFindMapError;
 use rt::time::local;
 use rt::time::Instant;
-use sched::scheduler::spawn;
+use sched::scheduler::create;
 use spin::{Mutex, Once};
 use user::objects::memory::Memory;
 use user::objects::memory::MemoryCreateError;
@@ -30,6 +30,7 @@ pub struct Thread {
     // warning: this mutex MUST unlock quickly after lock
     pub process: Arc<Process>,
     future: Once<Mutex<Pin<Box<dyn Future<Output = ()> + Send>>>>,
+    task: Once<Weak<Task>>,
 }
 
 impl Thread {
@@ -40,6 +41,7 @@ impl Thread {
         process: &Arc<Process>,
         pc: VAddr,
         opaque: usize,
+        init: impl FnOnce(VAddr) -> Vec<u8>,
     ) -> Result<Arc<Thread>, ThreadCreateError> {
         let sp = {
             let memory = Memory::create(config::THREAD_STACK_LAYOUT).out::<ThreadCreateError>()?;
@@ -47,10 +49,13 @@ impl Thread {
             let stack_bot = process
                 .space
                 .root
-                .find_map(memory, Permission::RW)
+                .find_map(memory.clone(), Permission::RW)
                 .out::<ThreadCreateError>()?;
             let stack_top = stack_bot + size;
-            stack_top - P::ABI_STACK_OFFSET
+            let content = init(stack_top);
+            assert!(content.len() % 16 == 0 && content.len() <= size);
+            memory.write(size - content.len(), &content);
+            stack_top - content.len() - P::ABI_STACK_OFFSET
         };
         let tp = match &process.load_tls {
             None => VAddr::new(0),
@@ -68,6 +73,7 @@ impl Thread {
             status: AtomicCell::new(ThreadStatus::Live),
             signal_set: SignalSet::new(),
             future: Once::new(),
+            task: Once::new(),
             trapping: Mutex::new(<P as Platform>::Trapping::new(
                 Privilege::User,
                 pc,
@@ -80,9 +86,14 @@ impl Thread {
         thread
             .future
             .call_once(|| Mutex::new(Environment::make(thread.clone())));
-        spawn(thread.clone(), Priority::DEFAULT);
+        let task = create(thread.clone(), Priority::DEFAULT);
+        thread.task.call_once(|| Arc::downgrade(&task));
+        task.resched();
         Ok(thread)
     }
+    pub fn task(&self) -> Option<Arc<Task>> {
+        self.task.get()?.upgrade()
+    }
 }
 
 impl PreemptiveFuture for Thread {
@@ -100,22 +111,24 @@ impl Environment {
             .store(ThreadStatus::Dead(ThreadDeath::Fault(
                 ThreadFault::ProcessDead,
             )));
-        self.thread
-            .process
-            .thread_set
-            .on_thread_killed(&self.thread);
+        self.on_thread_killed().await;
         Flow::Eff(Effect)
     }
     pub async fn thread_exit(&self, exit_code: isize) -> Flow<!> {
         self.thread
             .status
             .store(ThreadStatus::Dead(ThreadDeath::Exited(exit_code)));
-        self.thread
-            .process
-            .thread_set
-            .on_thread_killed(&self.thread);
+        self.on_thread_killed().await;
         Flow::Eff(Effect)
     }
+    async fn on_thread_killed(&self) {
+        let process = &self.thread.process;
+        if process.thread_set.on_thread_killed(&self.thread)
+            && Arc::ptr_eq(process, sched::scheduler::initproc())
+        {
+            sched::scheduler::on_initproc_exit().await;
+        }
+    }
     pub async fn forever(&self) -> Flow<!> {
         use Exception::*;
         use Interrupt::*;
@@ -157,13 +170,16 @@ impl Environment {
                     }
                 }
                 TrapInterrupt(Timer) => {
+                    rt::random::stir(rt::thread::current().id() as u64);
+                    drivers::manager::reseed();
                     base::future::yield_now().await;
                 }
                 TrapInterrupt(Software { .. }) => {
+                    P::ipi_clear();
                     self.handle_signals().await?;
                 }
-                TrapInterrupt(Hardware { value }) => {
-                    panic!("hardware interrupt: not supported (value = {})", value);
+                TrapInterrupt(Hardware { .. }) => {
+                    drivers::manager::interrupt();
                 }
             }
         }
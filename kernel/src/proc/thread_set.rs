@@ -22,9 +22,10 @@ impl ThreadSet {
             thread.signal_set.send(signal.clone());
         }
     }
-    pub fn on_thread_killed(&self, thread: &Arc<Thread>) {
+    pub fn on_thread_killed(&self, thread: &Arc<Thread>) -> bool {
         let mut inner = self.threads.lock();
         let value = inner.remove(&(Arc::as_ptr(thread) as usize)).unwrap();
         assert_eq!(Arc::as_ptr(&value), Arc::as_ptr(thread));
+        inner.is_empty()
     }
 }
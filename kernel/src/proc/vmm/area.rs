@@ -1,22 +1,37 @@
 use super::map::MapUser;
 use super::*;
 use crate::prelude::*;
+use core::sync::atomic::{AtomicBool, Ordering};
+use futures::future::{BoxFuture, FutureExt};
 use mem::pages::*;
 use rt::paging::Paging;
 
 pub struct Area {
     pub segment: Segment<VAddr>,
     pub page_table: Arc<<P as Platform>::Paging>,
+    pub randomized: Arc<AtomicBool>,
     pub page_allocator: Pages<Either<Arc<Area>, (Arc<dyn MapUser>, Permission)>>,
 }
 
 impl Area {
+    fn find(
+        &self,
+        guard: &PagesGuard<'_, Either<Arc<Area>, (Arc<dyn MapUser>, Permission)>>,
+        layout: MapLayout,
+    ) -> Result<Segment<VAddr>, PagesFindError> {
+        if self.randomized.load(Ordering::Relaxed) {
+            guard.find_random(layout, rt::random::next_u64())
+        } else {
+            guard.find(layout)
+        }
+    }
     pub fn create(&self, vaddr: VAddr, size: usize) -> Result<Arc<Area>, AreaCreateError> {
         use AreaCreateError::*;
         let segment = by_size(vaddr, size).ok_or(OutOfRange)?;
         let area = Arc::new(Area {
             segment,
             page_table: self.page_table.clone(),
+            randomized: self.randomized.clone(),
             page_allocator: Pages::new(segment)?,
         });
         self.page_allocator
@@ -26,10 +41,11 @@ impl Area {
     }
     pub fn find_create(&self, layout: MapLayout) -> Result<Arc<Area>, AreaFindCreateError> {
         let mut guard = self.page_allocator.lock();
-        let segment = guard.find(layout)?;
+        let segment = self.find(&guard, layout)?;
         let area = Arc::new(Area {
             segment,
             page_table: self.page_table.clone(),
+            randomized: self.randomized.clone(),
             page_allocator: Pages::new(segment)?,
         });
         guard
@@ -51,7 +67,7 @@ impl Area {
         self.page_allocator
             .lock()
             .acquire(segment, Right((map.clone(), permission)))?;
-        for i in 0..map.len() {
+        for i in (0..map.len()).filter(|&i| map.present(i)) {
             let vaddr = segment.start() + i * map.layout().align();
             let paddr = map.index(i);
             self.page_table
@@ -69,11 +85,11 @@ impl Area {
         ensure!(P::check_align(map.layout().align()), AlignNotSupported);
         ensure!(P::check_permission(permission), PermissionNotSupported);
         let mut guard = self.page_allocator.lock();
-        let segment = guard.find(map.layout())?;
+        let segment = self.find(&guard, map.layout())?;
         guard
             .acquire(segment, Right((map.clone(), permission)))
             .out::<AreaFindMapError>()?;
-        for i in 0..map.len() {
+        for i in (0..map.len()).filter(|&i| map.present(i)) {
             let vaddr = segment.start() + i * map.layout().align();
             let paddr = map.index(i);
             self.page_table
@@ -82,16 +98,69 @@ impl Area {
         }
         Ok(segment.start())
     }
-    pub fn unmap(&self, start: VAddr) -> Result<(), AreaUnmapError> {
+    pub fn mapped(&self, start: VAddr) -> Result<Arc<dyn MapUser>, AreaUnmapError> {
+        use AreaUnmapError::*;
+        match self.page_allocator.lock().get(start).ok_or(NotFound)? {
+            Left(_) => Err(UnmapAnArea),
+            Right((map, _)) => Ok(map.clone()),
+        }
+    }
+    pub fn unmap(&self, start: VAddr) -> Result<Arc<dyn MapUser>, AreaUnmapError> {
         use AreaUnmapError::*;
         let mut guard = self.page_allocator.lock();
         ensure!(guard.get(start).ok_or(NotFound)?.is_right(), UnmapAnArea);
         let (map, _) = guard.release(start).unwrap().unwrap_right();
-        for i in 0..map.len() {
+        for i in (0..map.len()).filter(|&i| map.present(i)) {
             let vaddr = start + i * map.layout().align();
-            self.page_table.unmap(vaddr, map.layout().align()).unwrap();
+            let (_, dirty) = self.page_table.unmap(vaddr, map.layout().align()).unwrap();
+            map.uninstall(i, dirty);
         }
-        Ok(())
+        Ok(map)
+    }
+    pub fn fault(&self, addr: VAddr, access: Access) -> BoxFuture<'_, Result<(), AreaFaultError>> {
+        async move {
+            use AreaFaultError::*;
+            let located = match self.page_allocator.lock().locate(addr) {
+                Some((_, Left(area))) => Left(area.clone()),
+                Some((segment, Right((map, permission)))) => {
+                    Right((segment, map.clone(), *permission))
+                }
+                None => return Err(NotFound),
+            };
+            let (segment, map, permission) = match located {
+                Left(area) => return area.fault(addr, access).await,
+                Right(x) => x,
+            };
+            let allowed = match access {
+                Access::Instruction => permission.execute,
+                Access::Load => permission.read,
+                Access::Store => permission.write,
+            };
+            ensure!(allowed, PermissionDenied);
+            let align = map.layout().align();
+            let i = (addr - segment.start()) / align;
+            map.fill(i).await.map_err(|_| IOError)?;
+            let guard = self.page_allocator.lock();
+            match guard.locate(addr) {
+                Some((current, Right((current_map, _))))
+                    if current == segment
+                        && Arc::as_ptr(current_map) as *const ()
+                            == Arc::as_ptr(&map) as *const () => {}
+                _ => return Ok(()),
+            }
+            let vaddr = segment.start() + i * align;
+            if let Some(paddr) = map.install(i, &self.page_table, vaddr) {
+                self.page_table
+                    .map(vaddr, paddr, align, permission, true, false)
+                    .unwrap();
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+    fn filled(map: &Arc<dyn MapUser>, offset: usize, len: usize) -> bool {
+        let align = map.layout().align();
+        (offset / align..(offset + len).div_ceil(align)).all(|i| map.filled(i))
     }
     pub fn read(&self, mut addr: VAddr, mut buffer: &mut [u8]) -> Result<(), AreaReadError> {
         use AreaReadError::*;
@@ -107,6 +176,7 @@ impl Area {
                 }
                 Right((map, permission)) => {
                     ensure!(permission.read, PermissionDenied);
+                    ensure!(Self::filled(map, addr - val.0.start(), len), BadRead);
                     map.read(addr - val.0.start(), &mut buffer[..len]);
                 }
             }
@@ -129,6 +199,7 @@ impl Area {
                 }
                 Right((map, permission)) => {
                     ensure!(permission.write, PermissionDenied);
+                    ensure!(Self::filled(map, addr - val.0.start(), len), BadWrite);
                     map.write(addr - val.0.start(), &buffer[..len]);
                 }
             }
@@ -1,3 +1,26 @@
 use crate::prelude::*;
 
-pub trait MapUser: Send + Sync + Map + MapRead + MapWrite + MapIndex {}
+#[async_trait::async_trait]
+pub trait MapUser: Send + Sync + Map + MapRead + MapWrite + MapIndex {
+    fn present(&self, _i: usize) -> bool {
+        true
+    }
+    fn filled(&self, _i: usize) -> bool {
+        true
+    }
+    fn install(
+        &self,
+        _i: usize,
+        _page_table: &Arc<<P as Platform>::Paging>,
+        _vaddr: VAddr,
+    ) -> Option<PAddr> {
+        None
+    }
+    fn uninstall(&self, _i: usize, _dirty: bool) {}
+    async fn fill(&self, _i: usize) -> Result<(), FsError> {
+        Ok(())
+    }
+    async fn sync(&self) -> Result<(), FsError> {
+        Ok(())
+    }
+}
@@ -48,6 +48,13 @@ pub enum AreaUnmapError {
     NotFound,
 }
 
+#[derive(Debug)]
+pub enum AreaFaultError {
+    NotFound,
+    PermissionDenied,
+    IOError,
+}
+
 #[derive(Debug)]
 pub enum AreaReadError {
     OutOfRange,
@@ -71,3 +78,13 @@ fully!(PagesAcquireError, AreaMapError; ZeroSize, OutOfRange, Overlapping);
 fully!(PagesFindError, AreaFindMapError; ZeroSize, OutOfVirtualMemory);
 fully!(PagesReleaseError, AreaUnmapError; NotFound);
 partially!(PagesAcquireError, AreaFindMapError; ZeroSize);
+fully!(AreaFaultError, AreaReadError;
+    NotFound => BadRead,
+    PermissionDenied => PermissionDenied,
+    IOError => BadRead
+);
+fully!(AreaFaultError, AreaWriteError;
+    NotFound => BadWrite,
+    PermissionDenied => PermissionDenied,
+    IOError => BadWrite
+);
@@ -1,6 +1,7 @@
 use crate::{mem::vmm::VMM, prelude::*};
+use core::sync::atomic::{AtomicBool, Ordering};
 use mem::pages::Pages;
-use proc::vmm::{Area, AreaReadError, AreaWriteError};
+use proc::vmm::{Area, AreaFaultError, AreaReadError, AreaWriteError};
 use rt::paging::Paging;
 
 pub struct UserSpace {
@@ -9,12 +10,13 @@ pub struct UserSpace {
 }
 
 impl UserSpace {
-    pub fn new() -> Arc<UserSpace> {
+    pub fn new(randomized: bool) -> Arc<UserSpace> {
         let page_table = Arc::new(<P as Platform>::Paging::new());
         Arc::new(UserSpace {
             root: Arc::new(Area {
                 segment: VMM.user_segment,
                 page_table: page_table.clone(),
+                randomized: Arc::new(AtomicBool::new(randomized)),
                 page_allocator: Pages::new(VMM.user_segment).unwrap(),
             }),
             page_table,
@@ -23,18 +25,44 @@ impl UserSpace {
     pub fn segment(&self) -> Segment<VAddr> {
         self.root.segment
     }
-    pub fn read_buffer(&self, addr: VAddr, buffer: &mut [u8]) -> Result<(), AreaReadError> {
+    pub fn set_randomized(&self, randomized: bool) {
+        self.root.randomized.store(randomized, Ordering::Relaxed);
+    }
+    pub async fn fault(&self, addr: VAddr, access: Access) -> Result<(), AreaFaultError> {
+        self.root.fault(addr, access).await
+    }
+    async fn prefault(
+        &self,
+        addr: VAddr,
+        len: usize,
+        access: Access,
+    ) -> Result<(), AreaFaultError> {
+        let start = addr.to_usize() & !4095;
+        let end = addr.to_usize().saturating_add(len);
+        for page in (start..end).step_by(4096) {
+            self.fault(VAddr::new(usize::max(page, addr.to_usize())), access)
+                .await?;
+        }
+        Ok(())
+    }
+    pub async fn read_buffer(&self, addr: VAddr, buffer: &mut [u8]) -> Result<(), AreaReadError> {
+        self.prefault(addr, buffer.len(), Access::Load).await?;
         self.root.read(addr, buffer)
     }
-    pub fn write_buffer(&self, addr: VAddr, buffer: &[u8]) -> Result<(), AreaWriteError> {
+    pub async fn write_buffer(&self, addr: VAddr, buffer: &[u8]) -> Result<(), AreaWriteError> {
+        self.prefault(addr, buffer.len(), Access::Store).await?;
         self.root.write(addr, buffer)
     }
 }
 
 impl Environment {
-    pub async fn handle_page_fault(&self, _addr: VAddr, access: Access) -> Flow<()> {
-        self.process_fault(ProcessFault::Segment { access })
-            .await
-            .map(|x| x)
+    pub async fn handle_page_fault(&self, addr: VAddr, access: Access) -> Flow<()> {
+        match self.process.space.fault(addr, access).await {
+            Ok(()) => Flow::Ok(()),
+            Err(_) => self
+                .process_fault(ProcessFault::Segment { access })
+                .await
+                .map(|x| x),
+        }
     }
 }
@@ -1,11 +1,17 @@
 use crate::prelude::*;
 use base::cell::SingletonCell;
 
+#[derive(Debug)]
+pub enum MemoryInitrdError {
+    Overlapping,
+}
+
 pub struct MemoryBuilder {
     pub start: PAddr,
     pub ptr: PAddr,
     pub end: PAddr,
     pub buffer: Option<Segment<PAddr>>,
+    pub initrd: Option<Segment<PAddr>>,
 }
 
 impl MemoryBuilder {
@@ -15,16 +21,25 @@ impl MemoryBuilder {
             ptr: segment.start(),
             end: segment.end().unwrap(),
             buffer: None,
+            initrd: None,
         }
     }
+    pub fn initrd(&mut self, segment: Segment<PAddr>) -> Result<(), MemoryInitrdError> {
+        assert!(self.initrd.is_none());
+        ensure!(self.disjoint(segment), MemoryInitrdError::Overlapping);
+        self.initrd = Some(segment);
+        Ok(())
+    }
     pub fn brk(&mut self, addr: PAddr) {
         self.ptr = core::cmp::max(self.ptr, addr);
         assert!(self.ptr <= self.end);
+        self.check();
     }
     pub fn sbrk(&mut self, size: usize) -> Segment<PAddr> {
         let ptr = self.ptr;
         self.ptr = ptr + size;
         assert!(self.ptr <= self.end);
+        self.check();
         by_size(ptr, size).unwrap()
     }
     pub fn alloc(&mut self, layout: MapLayout) -> PAddr {
@@ -32,8 +47,20 @@ impl MemoryBuilder {
         let ans = self.ptr;
         self.ptr = self.ptr + layout.size();
         assert!(self.ptr <= self.end);
+        self.check();
         ans
     }
+    fn disjoint(&self, initrd: Segment<PAddr>) -> bool {
+        self.ptr <= initrd.start() || initrd.wrapping_end() <= self.start
+    }
+    fn check(&mut self) {
+        if let Some(initrd) = self.initrd {
+            if !self.disjoint(initrd) {
+                warn!("initrd is overlapped by early allocations, booting without it");
+                self.initrd = None;
+            }
+        }
+    }
     pub fn alloc_buffer(&mut self) {
         let buffer = self.sbrk((self.end - self.start) / 4096 * 2);
         self.buffer = Some(buffer);
@@ -46,6 +73,7 @@ impl MemoryBuilder {
             ptr: self.ptr,
             end: self.end,
             buffer: self.buffer?,
+            initrd: self.initrd,
         })
     }
 }
@@ -55,6 +83,7 @@ pub struct Memory {
     pub ptr: PAddr,
     pub end: PAddr,
     pub buffer: Segment<PAddr>,
+    pub initrd: Option<Segment<PAddr>>,
 }
 
 static MEMORY: SingletonCell<Memory> = SingletonCell::new();
@@ -6,6 +6,7 @@ pub mod mem;
 pub mod paging;
 pub mod platform;
 pub mod process;
+pub mod random;
 pub mod thread;
 pub mod time;
 pub mod trap;
@@ -26,5 +26,6 @@ pub trait Paging: Debug + Send + Sync {
         user: bool,
         global: bool,
     ) -> Result<(), PagingMapError>;
-    fn unmap(&self, vaddr: VAddr, align: usize) -> Result<PAddr, PagingUnmapError>;
+    fn unmap(&self, vaddr: VAddr, align: usize) -> Result<(PAddr, bool), PagingUnmapError>;
+    fn clean(&self, vaddr: VAddr, paddr: PAddr, align: usize) -> bool;
 }
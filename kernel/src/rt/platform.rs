@@ -17,9 +17,17 @@ pub trait Platform {
     // functions
     fn id() -> usize;
     fn abort() -> !;
+    fn shutdown(code: isize) -> !;
+    fn reboot() -> !;
     fn write(s: &str);
     unsafe fn backtrace() -> ArrayVec<BacktraceFrame, { config::BACKTRACE }>;
     unsafe fn trap_switch(ctx: &mut Self::Trapping, pt: &Self::Paging) -> Trap;
+    fn interrupt_enable(value: usize);
+    fn interrupt_claim() -> Option<usize>;
+    fn interrupt_complete(value: usize);
+    fn wait();
+    fn ipi_send(id: usize);
+    fn ipi_clear();
 }
 
 pub struct P;
@@ -3,3 +3,11 @@ use crate::prelude::*;
 pub fn abort() -> ! {
     P::abort();
 }
+
+pub fn shutdown(code: isize) -> ! {
+    P::shutdown(code);
+}
+
+pub fn reboot() -> ! {
+    P::reboot();
+}
@@ -0,0 +1,117 @@
+use crate::prelude::*;
+use rt::time::Instant;
+use spin::Mutex;
+
+const CONSTANTS: [u32; 4] = [0x61707865, 0x3320646e, 0x79622d32, 0x6b206574];
+
+fn quarter(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+fn chacha20(key: &[u32; 8], counter: u64, nonce: u64) -> [u8; 64] {
+    let mut input = [0u32; 16];
+    input[..4].copy_from_slice(&CONSTANTS);
+    input[4..12].copy_from_slice(key);
+    input[12] = counter as u32;
+    input[13] = (counter >> 32) as u32;
+    input[14] = nonce as u32;
+    input[15] = (nonce >> 32) as u32;
+    let mut state = input;
+    for _ in 0..10 {
+        quarter(&mut state, 0, 4, 8, 12);
+        quarter(&mut state, 1, 5, 9, 13);
+        quarter(&mut state, 2, 6, 10, 14);
+        quarter(&mut state, 3, 7, 11, 15);
+        quarter(&mut state, 0, 5, 10, 15);
+        quarter(&mut state, 1, 6, 11, 12);
+        quarter(&mut state, 2, 7, 8, 13);
+        quarter(&mut state, 3, 4, 9, 14);
+    }
+    let mut output = [0u8; 64];
+    for (i, chunk) in output.chunks_exact_mut(4).enumerate() {
+        chunk.copy_from_slice(&state[i].wrapping_add(input[i]).to_le_bytes());
+    }
+    output
+}
+
+struct Pool {
+    key: [u32; 8],
+    counter: u64,
+}
+
+impl Pool {
+    const fn new() -> Self {
+        Self {
+            key: [0; 8],
+            counter: 0,
+        }
+    }
+    fn block(&mut self, nonce: u64) -> [u8; 64] {
+        let block = chacha20(&self.key, self.counter, nonce);
+        self.counter = self.counter.wrapping_add(1);
+        block
+    }
+    fn rekey(&mut self, nonce: u64) {
+        let block = self.block(nonce);
+        for (word, chunk) in self.key.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_le_bytes(chunk.try_into().unwrap());
+        }
+    }
+    fn feed(&mut self, data: &[u8]) {
+        for chunk in data.chunks(32) {
+            for (i, &byte) in chunk.iter().enumerate() {
+                self.key[i / 4] ^= (byte as u32) << (i % 4 * 8);
+            }
+            self.rekey(0);
+        }
+    }
+}
+
+static POOL: Mutex<Pool> = Mutex::new(Pool::new());
+
+fn jitter() -> u64 {
+    Instant::maybe_now().map_or(0, Instant::value)
+}
+
+pub fn init_global() {
+    let mut pool = POOL.lock();
+    let mut samples = Vec::with_capacity(config::RANDOM_JITTER_ROUNDS * 8);
+    for _ in 0..config::RANDOM_JITTER_ROUNDS {
+        pool.block(jitter());
+        samples.extend_from_slice(&jitter().to_le_bytes());
+    }
+    pool.feed(&samples);
+}
+
+pub fn stir(event: u64) {
+    let mut pool = POOL.lock();
+    pool.feed(&(jitter() ^ event.rotate_left(32)).to_le_bytes());
+}
+
+pub fn feed(data: &[u8]) {
+    let mut pool = POOL.lock();
+    pool.feed(&jitter().to_le_bytes());
+    pool.feed(data);
+}
+
+pub fn fill(buffer: &mut [u8]) {
+    let mut pool = POOL.lock();
+    let nonce = jitter();
+    for chunk in buffer.chunks_mut(64) {
+        chunk.copy_from_slice(&pool.block(nonce)[..chunk.len()]);
+    }
+    pool.rekey(nonce);
+}
+
+pub fn next_u64() -> u64 {
+    let mut bytes = [0u8; 8];
+    fill(&mut bytes);
+    u64::from_le_bytes(bytes)
+}
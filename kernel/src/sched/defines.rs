@@ -2,42 +2,17 @@ use crate::prelude::*;
 use core::task::{Context, Poll};
 use core::time::Duration;
 use crossbeam::atomic::AtomicCell;
+use rt::time::Instant;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Vruntime(u128);
+pub use nekos_sched::{Priority, Schedulable, ScheduleClass, Vruntime};
 
-impl Vruntime {
-    pub fn new(x: u128) -> Self {
-        Self(x)
-    }
-    pub fn value(self) -> u128 {
-        self.0
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Priority(u32);
-
-impl Priority {
-    pub const MIN: Self = Self(1);
-    pub const MAX: Self = Self(1000000);
-    pub const DEFAULT: Self = Self(1000);
-    pub fn new(x: u32) -> Option<Self> {
-        if Self::MIN.0 <= x && x <= Self::MAX.0 {
-            Some(Self(x))
-        } else {
-            None
-        }
-    }
-    pub fn value(self) -> u32 {
-        self.0
-    }
-}
-
-impl Default for Priority {
-    fn default() -> Self {
-        Self::DEFAULT
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitprocExitPolicy {
+    /// SBI system reset only carries a reason, so the exit code is reported
+    /// as NONE when it is zero and as SYSTEM_FAILURE otherwise.
+    PowerOff,
+    Reboot,
+    Panic,
 }
 
 pub trait PreemptiveFuture: Send + Sync {
@@ -48,6 +23,10 @@ pub struct Task {
     future: Arc<dyn PreemptiveFuture>,
     vruntime: AtomicCell<Vruntime>,
     priority: AtomicCell<Priority>,
+    class: AtomicCell<ScheduleClass>,
+    affinity: AtomicCell<usize>,
+    hart: AtomicCell<usize>,
+    queued: AtomicCell<bool>,
 }
 
 impl Task {
@@ -55,38 +34,73 @@ impl Task {
         future: Arc<dyn PreemptiveFuture>,
         vruntime: Vruntime,
         priority: Priority,
+        hart: usize,
     ) -> Arc<Task> {
         Arc::new(Task {
             future,
             vruntime: AtomicCell::new(vruntime),
             priority: AtomicCell::new(priority),
+            class: AtomicCell::new(ScheduleClass::Normal),
+            affinity: AtomicCell::new(usize::MAX),
+            hart: AtomicCell::new(hart),
+            queued: AtomicCell::new(false),
         })
     }
     pub fn poll(&self, cx: &mut Context, duration: Duration) {
-        let step = 1000000000u128 / self.priority().value() as u128;
+        let start = Instant::now();
         let _ = self.future.poll(cx, duration);
-        self.set_vruntime(Vruntime::new(self.vruntime().value() + step));
+        self.charge(Instant::now() - start);
     }
-    pub fn vruntime(&self) -> Vruntime {
-        self.vruntime.load()
+    pub fn set_priority(&self, priority: Priority) {
+        self.priority.store(priority);
     }
-    pub fn set_vruntime(&self, vruntime: Vruntime) {
-        self.vruntime.store(vruntime)
+    pub fn set_class(&self, class: ScheduleClass) {
+        self.class.store(class);
     }
-    pub fn priority(&self) -> Priority {
-        self.priority.load()
+    pub fn affinity(&self) -> usize {
+        self.affinity.load()
     }
-    pub fn set_priority(&self, priority: Priority) {
-        self.priority.store(priority);
+    pub fn set_affinity(&self, affinity: usize) {
+        self.affinity.store(affinity);
+    }
+    pub fn hart(&self) -> usize {
+        self.hart.load()
+    }
+    pub fn set_hart(&self, hart: usize) {
+        self.hart.store(hart);
     }
     pub fn resched(self: Arc<Task>) {
-        let step = 1000000000u128 / self.priority().value() as u128;
-        let mut queue = crate::sched::scheduler::SCHEDULER.queue.lock();
-        if let Some(queue_vruntime) = queue.vruntime() {
-            let limit_num = queue_vruntime.value() + step;
-            let limit = Vruntime::new(limit_num);
-            self.set_vruntime(core::cmp::max(self.vruntime(), limit));
+        if self.queued.load() {
+            return;
         }
-        queue.insert(self);
+        let scheduler = &crate::sched::scheduler::SCHEDULER;
+        let hart = scheduler.home(&self);
+        self.set_hart(hart);
+        scheduler.hart(hart).with(|queue| {
+            queue.place(&self, config::SCHEDULE_SLEEPER_CREDIT);
+            queue.insert(self);
+        });
+        scheduler.notify(hart);
+    }
+}
+
+impl Schedulable for Task {
+    fn class(&self) -> ScheduleClass {
+        self.class.load()
+    }
+    fn priority(&self) -> Priority {
+        self.priority.load()
+    }
+    fn vruntime(&self) -> Vruntime {
+        self.vruntime.load()
+    }
+    fn set_vruntime(&self, vruntime: Vruntime) {
+        self.vruntime.store(vruntime)
+    }
+    fn allowed(&self, hart: usize) -> bool {
+        hart < usize::BITS as usize && self.affinity() & 1 << hart != 0
+    }
+    fn swap_queued(&self, queued: bool) -> bool {
+        self.queued.swap(queued)
     }
 }
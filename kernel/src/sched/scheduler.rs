@@ -1,9 +1,15 @@
 use crate::prelude::*;
 use alloc::collections::BTreeMap;
 use base::cell::SingletonCell;
+use core::future::Future;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::task::{Context, Poll};
+use core::time::Duration;
+use futures::future::{BoxFuture, FutureExt};
 use futures::task::ArcWake;
+use nekos_sched::SchedulerQueue;
 use proc::process::Process;
-use spin::{Lazy, Mutex};
+use spin::Mutex;
 
 impl ArcWake for Task {
     fn wake(self: Arc<Self>) {
@@ -14,81 +20,255 @@ impl ArcWake for Task {
     }
 }
 
-pub struct SchedulerQueue {
-    ready: BTreeMap<(Vruntime, usize), Arc<Task>>,
+pub struct HartQueue {
+    queue: Mutex<SchedulerQueue<Task>>,
+    load: AtomicUsize,
+    idle: AtomicBool,
 }
 
-impl SchedulerQueue {
-    pub fn new() -> Self {
+impl HartQueue {
+    fn new() -> Self {
         Self {
-            ready: BTreeMap::new(),
+            queue: Mutex::new(SchedulerQueue::new()),
+            load: AtomicUsize::new(0),
+            idle: AtomicBool::new(false),
         }
     }
-    pub fn vruntime(&mut self) -> Option<Vruntime> {
-        self.ready
-            .first_key_value()
-            .map(|((vruntime, _), _)| *vruntime)
+    pub fn with<R>(&self, f: impl FnOnce(&mut SchedulerQueue<Task>) -> R) -> R {
+        let mut queue = self.queue.lock();
+        let r = f(&mut queue);
+        self.load.store(queue.count(), Ordering::SeqCst);
+        r
     }
-    pub fn insert(&mut self, task: Arc<Task>) {
-        self.ready
-            .insert((task.vruntime(), Arc::as_ptr(&task) as usize), task);
-    }
-    pub fn pop(&mut self) -> Option<Arc<Task>> {
-        self.ready.pop_first().map(|(_, value)| value)
+    pub fn load(&self) -> usize {
+        self.load.load(Ordering::SeqCst)
     }
 }
 
 pub struct Scheduler {
-    pub queue: Mutex<SchedulerQueue>,
+    harts: BTreeMap<usize, HartQueue>,
 }
 
 impl Scheduler {
-    pub fn push(&self, task: Arc<Task>) {
-        self.queue.lock().insert(task);
+    pub fn hart(&self, id: usize) -> &HartQueue {
+        &self.harts[&id]
+    }
+    pub fn pop(&self, id: usize) -> Option<Arc<Task>> {
+        let local = self.hart(id);
+        let busiest = self
+            .harts
+            .iter()
+            .filter(|(&x, _)| x != id)
+            .max_by_key(|(_, queue)| queue.load());
+        if let Some((&from, queue)) = busiest {
+            let (load, other) = (local.load(), queue.load());
+            if (load == 0 && other != 0) || other > load + 1 {
+                self.migrate(from, id);
+            }
+        }
+        loop {
+            let task = local.with(SchedulerQueue::pop)?;
+            if task.allowed(id) {
+                return Some(task);
+            }
+            task.resched();
+        }
+    }
+    pub fn home(&self, task: &Task) -> usize {
+        let hart = task.hart();
+        if task.allowed(hart) {
+            return hart;
+        }
+        self.harts
+            .iter()
+            .filter(|(&x, _)| task.allowed(x))
+            .min_by_key(|(_, queue)| queue.load())
+            .map_or(hart, |(&x, _)| x)
     }
-    pub fn pop(&self) -> Option<Arc<Task>> {
-        self.queue.lock().pop()
+    pub fn notify(&self, id: usize) {
+        let current = rt::thread::current().id();
+        let target = if self.hart(id).idle.load(Ordering::SeqCst) {
+            Some(id)
+        } else {
+            self.harts
+                .iter()
+                .find(|(_, queue)| queue.idle.load(Ordering::SeqCst))
+                .map(|(&x, _)| x)
+        };
+        if let Some(target) = target.filter(|&x| x != current) {
+            P::ipi_send(target);
+        }
+    }
+    fn idle(&self, id: usize) {
+        let local = self.hart(id);
+        local.idle.store(true, Ordering::SeqCst);
+        let runnable = local.load() != 0
+            || self
+                .harts
+                .iter()
+                .filter(|(&x, queue)| x != id && queue.load() != 0)
+                .any(|(_, queue)| queue.with(|queue| queue.stealable(id)));
+        if !runnable {
+            rt::time::local().timer(u64::MAX);
+            P::wait();
+        }
+        P::ipi_clear();
+        local.idle.store(false, Ordering::SeqCst);
+    }
+    fn migrate(&self, from: usize, to: usize) {
+        let stolen = self
+            .hart(from)
+            .with(|queue| Some((queue.steal(to)?, queue.min_vruntime())));
+        if let Some((task, base)) = stolen {
+            task.set_hart(to);
+            self.hart(to).with(|queue| {
+                let vruntime = task.vruntime().value() + queue.min_vruntime().value();
+                task.set_vruntime(Vruntime::new(vruntime.saturating_sub(base.value())));
+                queue.insert(task);
+            });
+        }
     }
 }
 
 pub(in crate::sched) static SCHEDULER: SingletonCell<Scheduler> = SingletonCell::new();
 
+pub fn create(future: Arc<dyn PreemptiveFuture>, priority: Priority) -> Arc<Task> {
+    let (&hart, queue) = SCHEDULER
+        .harts
+        .iter()
+        .min_by_key(|(_, queue)| queue.load())
+        .unwrap();
+    let vruntime = queue.with(|queue| queue.min_vruntime());
+    Task::new(future, vruntime, priority, hart)
+}
+
 pub fn spawn(future: Arc<dyn PreemptiveFuture>, priority: Priority) -> Arc<Task> {
-    let mut queue = SCHEDULER.queue.lock();
-    // todo: fix the bad behavior if all threads are blocked
-    let vruntime = queue.vruntime().unwrap_or_else(|| Vruntime::new(0));
-    let task = Task::new(future, vruntime, priority);
-    queue.insert(task.clone());
+    let task = create(future, priority);
+    task.clone().resched();
     task
 }
 
+struct Detached(Mutex<Option<BoxFuture<'static, ()>>>);
+
+impl PreemptiveFuture for Detached {
+    fn poll(&self, cx: &mut Context, _: Duration) -> Poll<()> {
+        let mut future = self.0.lock();
+        if let Some(Poll::Pending) = future.as_mut().map(|x| x.as_mut().poll(cx)) {
+            return Poll::Pending;
+        }
+        *future = None;
+        Poll::Ready(())
+    }
+}
+
+pub fn detach(future: impl Future<Output = ()> + Send + 'static) {
+    spawn(
+        Arc::new(Detached(Mutex::new(Some(future.boxed())))),
+        Priority::DEFAULT,
+    );
+}
+
 pub unsafe fn init_global() {
     SCHEDULER.initialize(Scheduler {
-        queue: Mutex::new(SchedulerQueue::new()),
+        harts: rt::thread::threads()
+            .keys()
+            .map(|&id| (id, HartQueue::new()))
+            .collect(),
     });
 }
 
-static INITPROC: Lazy<Arc<Process>> =
-    Lazy::new(|| Process::create("initproc").expect("initproc created failed"));
+static INITPROC: SingletonCell<Arc<Process>> = SingletonCell::new();
+
+pub fn init_initproc() {
+    let initproc = base::future::block_on(Process::create(
+        "/initproc",
+        &["/initproc"],
+        &[],
+        config::PROCESS_RANDOMIZED,
+    ))
+    .expect("initproc created failed");
+    INITPROC.initialize(initproc);
+}
 
 pub fn initproc() -> &'static Arc<Process> {
     &INITPROC
 }
 
+async fn flush() {
+    if let Err(e) = fs::vfs::sync().await {
+        warn!("failed to sync file systems, reason = {:?}", e);
+    }
+}
+
+pub async fn on_initproc_exit() -> ! {
+    let status = initproc().status();
+    let code = match status {
+        ProcessStatus::Live => 0,
+        ProcessStatus::Dead(ProcessDeath::Exited(code)) => code,
+        ProcessStatus::Dead(ProcessDeath::Fault(_)) => -1,
+    };
+    match config::INITPROC_EXIT_POLICY {
+        InitprocExitPolicy::PowerOff => {
+            info!("initproc exited, status = {:?}, powering off", status);
+            flush().await;
+            rt::process::shutdown(code)
+        }
+        InitprocExitPolicy::Reboot => {
+            info!("initproc exited, status = {:?}, rebooting", status);
+            flush().await;
+            rt::process::reboot()
+        }
+        InitprocExitPolicy::Panic => panic!("initproc exited unexpectedly, status = {:?}", status),
+    }
+}
+
 pub fn forever() -> ! {
     loop {
-        // todo: fix it
-        if initproc().is_dead() || initproc().thread_set.threads.lock().len() == 0 {
-            panic!(
-                "initproc exited unexpectedly, status = {:?}",
-                initproc().status()
-            );
-        }
-        if let Some(task) = SCHEDULER.pop() {
+        let hart = rt::thread::current().id();
+        if let Some(task) = SCHEDULER.pop(hart) {
+            task.set_hart(hart);
             let duration = config::SCHEDULE_TIMESLICE;
             let waker = futures::task::waker(task.clone());
             let cx = &mut core::task::Context::from_waker(&waker);
             task.poll(cx, duration);
+        } else {
+            drivers::manager::interrupt();
+            drivers::manager::reseed();
+            if !mem::frames::refill() {
+                SCHEDULER.idle(hart);
+            }
         }
     }
 }
+
+#[cfg(test)]
+struct Busy;
+
+#[cfg(test)]
+impl PreemptiveFuture for Busy {
+    fn poll(&self, _: &mut Context, duration: Duration) -> Poll<()> {
+        let start = rt::time::Instant::now();
+        while rt::time::Instant::now() - start < duration {
+            core::hint::spin_loop();
+        }
+        Poll::Ready(())
+    }
+}
+
+#[cfg(test)]
+#[test_case]
+fn poll_charges_elapsed_time() {
+    let waker = futures::task::noop_waker();
+    let cx = &mut Context::from_waker(&waker);
+    let normal = Task::new(Arc::new(Busy), Vruntime::new(0), Priority::DEFAULT, 0);
+    normal.poll(cx, Duration::from_millis(2));
+    assert!(normal.vruntime() >= Vruntime::new(2000000));
+    let heavy = Task::new(
+        Arc::new(Busy),
+        Vruntime::new(0),
+        Priority::new(4000).unwrap(),
+        0,
+    );
+    heavy.poll(cx, Duration::from_millis(2));
+    assert!(heavy.vruntime() >= Vruntime::new(500000));
+}
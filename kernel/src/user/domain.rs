@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use user::objects::file::FileFlags;
 
 impl DomainError for ! {
     fn into_u8(self) -> u8 {
@@ -27,6 +28,28 @@ impl Domain for isize {
     }
 }
 
+#[repr(u8)]
+pub enum DomainBoolError {
+    Invaild = 0,
+}
+
+impl DomainError for DomainBoolError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+impl Domain for bool {
+    type Error = DomainBoolError;
+    fn from_arguments(_: &Environment, x: usize) -> Flow<Self, Either<GeneralError, Self::Error>> {
+        match x {
+            0 => Flow::Ok(false),
+            1 => Flow::Ok(true),
+            _ => Flow::Err(DomainBoolError::Invaild.into()),
+        }
+    }
+}
+
 impl Domain for VAddr {
     type Error = !;
     fn from_arguments(_: &Environment, x: usize) -> Flow<Self, Either<GeneralError, Self::Error>> {
@@ -60,6 +83,79 @@ impl Domain for Permission {
     }
 }
 
+#[repr(u8)]
+pub enum DomainScheduleClassError {
+    Invaild = 0,
+}
+
+impl DomainError for DomainScheduleClassError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+impl Domain for ScheduleClass {
+    type Error = DomainScheduleClassError;
+    fn from_arguments(_: &Environment, x: usize) -> Flow<Self, Either<GeneralError, Self::Error>> {
+        match u8::try_from(x)
+            .ok()
+            .and_then(|x| ScheduleClass::try_from(x).ok())
+        {
+            Some(class) => Flow::Ok(class),
+            None => Flow::Err(DomainScheduleClassError::Invaild.into()),
+        }
+    }
+}
+
+#[repr(u8)]
+pub enum DomainPriorityError {
+    Invaild = 0,
+}
+
+impl DomainError for DomainPriorityError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+impl Domain for Priority {
+    type Error = DomainPriorityError;
+    fn from_arguments(_: &Environment, x: usize) -> Flow<Self, Either<GeneralError, Self::Error>> {
+        match u32::try_from(x).ok().and_then(Priority::new) {
+            Some(priority) => Flow::Ok(priority),
+            None => Flow::Err(DomainPriorityError::Invaild.into()),
+        }
+    }
+}
+
+#[repr(u8)]
+pub enum DomainFileFlagsError {
+    Invaild = 0,
+}
+
+impl DomainError for DomainFileFlagsError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+impl Domain for FileFlags {
+    type Error = DomainFileFlagsError;
+    fn from_arguments(_: &Environment, x: usize) -> Flow<Self, Either<GeneralError, Self::Error>> {
+        if x & !0b11111 == 0 {
+            Flow::Ok(Self {
+                read: (x & 0b00001) != 0,
+                write: (x & 0b00010) != 0,
+                create: (x & 0b00100) != 0,
+                truncate: (x & 0b01000) != 0,
+                append: (x & 0b10000) != 0,
+            })
+        } else {
+            Flow::Err(DomainFileFlagsError::Invaild.into())
+        }
+    }
+}
+
 #[repr(u8)]
 pub enum DomainHandleError {
     NotFound = 0,
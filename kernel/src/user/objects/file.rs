@@ -0,0 +1,98 @@
+use crate::prelude::*;
+use base::lock::AsyncMutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileFlags {
+    pub read: bool,
+    pub write: bool,
+    pub create: bool,
+    pub truncate: bool,
+    pub append: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom {
+    Start(u64),
+    Current(i64),
+    End(i64),
+}
+
+fn shift(base: u64, delta: i64) -> Option<u64> {
+    if delta >= 0 {
+        base.checked_add(delta as u64)
+    } else {
+        base.checked_sub(delta.unsigned_abs())
+    }
+}
+
+pub struct File {
+    inode: Arc<dyn Inode>,
+    flags: FileFlags,
+    offset: AsyncMutex<u64>,
+}
+
+impl File {
+    pub async fn open(path: &str, flags: FileFlags) -> Result<Arc<File>, FsError> {
+        let inode = match fs::vfs::lookup(path).await {
+            Ok(inode) => inode,
+            Err(FsError::NotFound) if flags.create => {
+                fs::vfs::create(path, InodeType::File).await?
+            }
+            Err(e) => return Err(e),
+        };
+        if inode.metadata().typa == InodeType::Directory {
+            ensure!(!flags.write, FsError::IsADirectory);
+        } else if flags.write && flags.truncate {
+            fs::vfs::truncate(&inode, 0).await?;
+        }
+        Ok(Arc::new(File {
+            inode,
+            flags,
+            offset: AsyncMutex::new(0),
+        }))
+    }
+    pub fn inode(&self) -> &Arc<dyn Inode> {
+        &self.inode
+    }
+    pub fn flags(&self) -> FileFlags {
+        self.flags
+    }
+    pub fn metadata(&self) -> Metadata {
+        self.inode.metadata()
+    }
+    pub async fn read(&self, buffer: &mut [u8]) -> Result<usize, FsError> {
+        ensure!(self.flags.read, FsError::PermissionDenied);
+        let mut offset = self.offset.lock().await;
+        let len = self.inode.read_at(*offset, buffer).await?;
+        *offset += len as u64;
+        Ok(len)
+    }
+    pub async fn write(&self, buffer: &[u8]) -> Result<usize, FsError> {
+        ensure!(self.flags.write, FsError::PermissionDenied);
+        let mut offset = self.offset.lock().await;
+        if self.flags.append {
+            *offset = self.inode.metadata().size;
+        }
+        let len = self.inode.write_at(*offset, buffer).await?;
+        if let Some(cached) = fs::cache::cache().lookup(&self.inode) {
+            cached.update(*offset, &buffer[..len]);
+        }
+        *offset += len as u64;
+        Ok(len)
+    }
+    pub async fn seek(&self, from: SeekFrom) -> Option<u64> {
+        let mut offset = self.offset.lock().await;
+        let new = match from {
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::Current(x) => shift(*offset, x),
+            SeekFrom::End(x) => shift(self.inode.metadata().size, x),
+        }?;
+        *offset = new;
+        Some(new)
+    }
+    pub async fn read_dir(&self, index: usize) -> Result<Option<DirEntry>, FsError> {
+        ensure!(self.flags.read, FsError::PermissionDenied);
+        let entries = self.inode.readdir().await?;
+        Ok(entries.into_iter().nth(index))
+    }
+}
@@ -22,7 +22,7 @@ impl Memory {
         let mut points = Vec::new();
         points.reserve(layout.size() / layout.align());
         for _ in 0..layout.size() / layout.align() {
-            match frames::alloc(point) {
+            match frames::alloc_zeroed(point) {
                 Ok(paddr) => {
                     points.push(paddr);
                 }
@@ -43,6 +43,17 @@ impl Memory {
     }
 }
 
+impl Drop for Memory {
+    fn drop(&mut self) {
+        let point = MapLayout::new(self.layout.align(), self.layout.align()).unwrap();
+        for &paddr in self.paddr.iter() {
+            unsafe {
+                frames::dealloc(paddr, point);
+            }
+        }
+    }
+}
+
 impl Map for Memory {
     fn layout(&self) -> MapLayout {
         self.layout
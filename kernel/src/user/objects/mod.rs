@@ -1,2 +1,3 @@
 pub mod channel;
+pub mod file;
 pub mod memory;
@@ -24,6 +24,7 @@ impl Syscalls<{ Syscall::DEBUG_WRITE }> for Syscall {
         env.process
             .space
             .read_buffer(buffer_addr, &mut buffer)
+            .await
             .unwrap();
         let o = core::str::from_utf8(&buffer).map_err(|_| InvaildString)?;
         print!("{}", o);
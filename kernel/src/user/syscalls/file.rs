@@ -0,0 +1,318 @@
+use crate::prelude::*;
+use user::objects::file::{File, FileFlags, SeekFrom};
+
+impl Object for File {}
+
+impl_syscall!(FILE_OPEN, 0x0324aac3u32);
+
+#[repr(u8)]
+pub enum FileOpenError {
+    BadBuffer,
+    InvaildPath,
+    NotFound,
+    NotADirectory,
+    IsADirectory,
+    ReadOnly,
+    PermissionDenied,
+    NoSpace,
+    NotSupported,
+    IOError,
+}
+
+impl SyscallError for FileOpenError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+fully!(FsError, FileOpenError;
+    NotFound => NotFound,
+    NotADirectory => NotADirectory,
+    IsADirectory => IsADirectory,
+    AlreadyExists => IOError,
+    NotEmpty => IOError,
+    BadPath => InvaildPath,
+    ReadOnly => ReadOnly,
+    PermissionDenied => PermissionDenied,
+    NoSpace => NoSpace,
+    Busy => IOError,
+    NotSupported => NotSupported,
+    BadFileSystem => IOError,
+    IOError => IOError
+);
+
+#[async_trait::async_trait]
+impl Syscalls<{ Syscall::FILE_OPEN }> for Syscall {
+    type Domain0 = VAddr;
+    type Domain1 = usize;
+    type Domain2 = FileFlags;
+    type Codomain = usize;
+    type Error = FileOpenError;
+    async fn syscall(
+        env: &Environment,
+        (path_addr, path_len, flags, ..): domain!(),
+    ) -> codomain!() {
+        use FileOpenError::*;
+        if path_len > config::PATH_MAX {
+            return Flow::Err(InvaildPath.into());
+        }
+        let mut buffer = vec![0u8; path_len];
+        env.process
+            .space
+            .read_buffer(path_addr, &mut buffer)
+            .await
+            .map_err(|_| BadBuffer)?;
+        let path = core::str::from_utf8(&buffer).map_err(|_| InvaildPath)?;
+        let file = File::open(path, flags).await.map_err(FileOpenError::from)?;
+        let handle_id = env.process.handle_set.push(Handle::new(file));
+        Flow::Ok(handle_id)
+    }
+}
+
+impl_syscall!(FILE_READ, 0x783646bfu32);
+
+#[repr(u8)]
+pub enum FileReadError {
+    BadBuffer,
+    PermissionDenied,
+    IsADirectory,
+    IOError,
+}
+
+impl SyscallError for FileReadError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+fully!(FsError, FileReadError;
+    NotFound => IOError,
+    NotADirectory => IOError,
+    IsADirectory => IsADirectory,
+    AlreadyExists => IOError,
+    NotEmpty => IOError,
+    BadPath => IOError,
+    ReadOnly => IOError,
+    PermissionDenied => PermissionDenied,
+    NoSpace => IOError,
+    Busy => IOError,
+    NotSupported => IOError,
+    BadFileSystem => IOError,
+    IOError => IOError
+);
+
+#[async_trait::async_trait]
+impl Syscalls<{ Syscall::FILE_READ }> for Syscall {
+    type Domain0 = Handle<File>;
+    type Domain1 = VAddr;
+    type Domain2 = usize;
+    type Codomain = usize;
+    type Error = FileReadError;
+    async fn syscall(
+        env: &Environment,
+        (file, buffer_addr, buffer_len, ..): domain!(),
+    ) -> codomain!() {
+        use FileReadError::*;
+        let mut buffer = vec![0u8; usize::min(buffer_len, config::FILE_IO_MAX)];
+        let len = file.read(&mut buffer).await.map_err(FileReadError::from)?;
+        env.process
+            .space
+            .write_buffer(buffer_addr, &buffer[..len])
+            .await
+            .map_err(|_| BadBuffer)?;
+        Flow::Ok(len)
+    }
+}
+
+impl_syscall!(FILE_WRITE, 0x1cc62be5u32);
+
+#[repr(u8)]
+pub enum FileWriteError {
+    BadBuffer,
+    PermissionDenied,
+    IsADirectory,
+    ReadOnly,
+    NoSpace,
+    IOError,
+}
+
+impl SyscallError for FileWriteError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+fully!(FsError, FileWriteError;
+    NotFound => IOError,
+    NotADirectory => IOError,
+    IsADirectory => IsADirectory,
+    AlreadyExists => IOError,
+    NotEmpty => IOError,
+    BadPath => IOError,
+    ReadOnly => ReadOnly,
+    PermissionDenied => PermissionDenied,
+    NoSpace => NoSpace,
+    Busy => IOError,
+    NotSupported => ReadOnly,
+    BadFileSystem => IOError,
+    IOError => IOError
+);
+
+#[async_trait::async_trait]
+impl Syscalls<{ Syscall::FILE_WRITE }> for Syscall {
+    type Domain0 = Handle<File>;
+    type Domain1 = VAddr;
+    type Domain2 = usize;
+    type Codomain = usize;
+    type Error = FileWriteError;
+    async fn syscall(
+        env: &Environment,
+        (file, buffer_addr, buffer_len, ..): domain!(),
+    ) -> codomain!() {
+        use FileWriteError::*;
+        let mut buffer = vec![0u8; usize::min(buffer_len, config::FILE_IO_MAX)];
+        env.process
+            .space
+            .read_buffer(buffer_addr, &mut buffer)
+            .await
+            .map_err(|_| BadBuffer)?;
+        let len = file.write(&buffer).await.map_err(FileWriteError::from)?;
+        Flow::Ok(len)
+    }
+}
+
+impl_syscall!(FILE_SEEK, 0xc393fd0eu32);
+
+#[repr(u8)]
+pub enum FileSeekError {
+    InvaildWhence,
+    InvaildOffset,
+}
+
+impl SyscallError for FileSeekError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+#[async_trait::async_trait]
+impl Syscalls<{ Syscall::FILE_SEEK }> for Syscall {
+    type Domain0 = Handle<File>;
+    type Domain1 = isize;
+    type Domain2 = usize;
+    type Codomain = usize;
+    type Error = FileSeekError;
+    async fn syscall(_: &Environment, (file, offset, whence, ..): domain!()) -> codomain!() {
+        use FileSeekError::*;
+        let from = match whence {
+            0 if offset >= 0 => SeekFrom::Start(offset as u64),
+            0 => return Flow::Err(InvaildOffset.into()),
+            1 => SeekFrom::Current(offset as i64),
+            2 => SeekFrom::End(offset as i64),
+            _ => return Flow::Err(InvaildWhence.into()),
+        };
+        let new = file.seek(from).await.ok_or(InvaildOffset)?;
+        Flow::Ok(usize::try_from(new).map_err(|_| InvaildOffset)?)
+    }
+}
+
+impl_syscall!(FILE_STAT, 0x6490fd4au32);
+
+#[repr(u8)]
+pub enum FileStatError {
+    BadBuffer,
+}
+
+impl SyscallError for FileStatError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+#[async_trait::async_trait]
+impl Syscalls<{ Syscall::FILE_STAT }> for Syscall {
+    type Domain0 = Handle<File>;
+    type Domain1 = VAddr;
+    type Error = FileStatError;
+    async fn syscall(env: &Environment, (file, stat_addr, ..): domain!()) -> codomain!() {
+        use FileStatError::*;
+        let metadata = file.metadata();
+        let mut buffer = [0u8; 24];
+        buffer[0..8].copy_from_slice(&metadata.ino.to_le_bytes());
+        buffer[8..16].copy_from_slice(&(metadata.typa as u64).to_le_bytes());
+        buffer[16..24].copy_from_slice(&metadata.size.to_le_bytes());
+        env.process
+            .space
+            .write_buffer(stat_addr, &buffer)
+            .await
+            .map_err(|_| BadBuffer)?;
+        Flow::Ok(())
+    }
+}
+
+impl_syscall!(DIR_READ, 0x240f16a7u32);
+
+#[repr(u8)]
+pub enum DirReadError {
+    BadBuffer,
+    BufferTooSmall,
+    PermissionDenied,
+    NotADirectory,
+    IOError,
+}
+
+impl SyscallError for DirReadError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+fully!(FsError, DirReadError;
+    NotFound => IOError,
+    NotADirectory => NotADirectory,
+    IsADirectory => IOError,
+    AlreadyExists => IOError,
+    NotEmpty => IOError,
+    BadPath => IOError,
+    ReadOnly => IOError,
+    PermissionDenied => PermissionDenied,
+    NoSpace => IOError,
+    Busy => IOError,
+    NotSupported => IOError,
+    BadFileSystem => IOError,
+    IOError => IOError
+);
+
+#[async_trait::async_trait]
+impl Syscalls<{ Syscall::DIR_READ }> for Syscall {
+    type Domain0 = Handle<File>;
+    type Domain1 = usize;
+    type Domain2 = VAddr;
+    type Domain3 = usize;
+    type Codomain = usize;
+    type Error = DirReadError;
+    async fn syscall(
+        env: &Environment,
+        (file, index, buffer_addr, buffer_len, ..): domain!(),
+    ) -> codomain!() {
+        use DirReadError::*;
+        let entry = match file.read_dir(index).await.map_err(DirReadError::from)? {
+            Some(entry) => entry,
+            None => return Flow::Ok(0),
+        };
+        let mut buffer = Vec::with_capacity(16 + entry.name.len());
+        buffer.extend_from_slice(&entry.ino.to_le_bytes());
+        buffer.extend_from_slice(&(entry.typa as u32).to_le_bytes());
+        buffer.extend_from_slice(&(entry.name.len() as u32).to_le_bytes());
+        buffer.extend_from_slice(entry.name.as_bytes());
+        if buffer.len() > buffer_len {
+            return Flow::Err(BufferTooSmall.into());
+        }
+        env.process
+            .space
+            .write_buffer(buffer_addr, &buffer)
+            .await
+            .map_err(|_| BadBuffer)?;
+        Flow::Ok(buffer.len())
+    }
+}
@@ -1,5 +1,7 @@
 use crate::prelude::*;
+use fs::mapping::FileMapping;
 use proc::vmm::*;
+use user::objects::file::File;
 use user::objects::memory::Memory;
 
 impl Object for Area {}
@@ -119,3 +121,106 @@ impl Syscalls<{ Syscall::AREA_MAP }> for Syscall {
         Flow::Ok(())
     }
 }
+
+impl_syscall!(AREA_MAP_FILE, 0x8f1d2c6au32);
+
+#[repr(u8)]
+pub enum AreaMapFileError {
+    ZeroSize,
+    OutOfRange,
+    Overlapping,
+    BadAddress,
+    AlignNotSupported,
+    PermissionNotSupported,
+    PermissionDenied,
+    IsADirectory,
+    InvaildOffset,
+    OutOfMemory,
+}
+
+impl SyscallError for AreaMapFileError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+#[async_trait::async_trait]
+impl Syscalls<{ Syscall::AREA_MAP_FILE }> for Syscall {
+    type Domain0 = Handle<Area>;
+    type Domain1 = Handle<File>;
+    type Domain2 = VAddr;
+    type Domain3 = usize;
+    type Domain4 = usize;
+    type Domain5 = Permission;
+    type Error = AreaMapFileError;
+    async fn syscall(
+        _: &Environment,
+        (area, file, addr, offset, size, permission): domain!(),
+    ) -> codomain!() {
+        use proc::vmm::AreaMapError as E;
+        use AreaMapFileError::*;
+        if file.metadata().typa != InodeType::File {
+            return Flow::Err(IsADirectory.into());
+        }
+        let flags = file.flags();
+        if (permission.read || permission.execute) && !flags.read
+            || permission.write && !flags.write
+        {
+            return Flow::Err(PermissionDenied.into());
+        }
+        if size == 0 {
+            return Flow::Err(ZeroSize.into());
+        }
+        let mapping: Arc<dyn MapUser> = match file.inode().mapping(offset as u64, size) {
+            Ok(mapping) => mapping,
+            Err(FsError::NoSpace) => return Flow::Err(OutOfMemory.into()),
+            Err(_) => FileMapping::new(file.inode(), offset as u64, size, permission.write)
+                .ok_or(InvaildOffset)?,
+        };
+        area.map(addr, mapping, permission).map_err(|e| match e {
+            E::ZeroSize => ZeroSize,
+            E::OutOfRange => OutOfRange,
+            E::Overlapping => Overlapping,
+            E::BadAddress => BadAddress,
+            E::AlignNotSupported => AlignNotSupported,
+            E::PermissionNotSupported => PermissionNotSupported,
+        })?;
+        Flow::Ok(())
+    }
+}
+
+impl_syscall!(AREA_UNMAP, 0x3e7b90d5u32);
+
+#[repr(u8)]
+pub enum AreaUnmapError {
+    UnmapAnArea,
+    NotFound,
+    IOError,
+}
+
+impl SyscallError for AreaUnmapError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+#[async_trait::async_trait]
+impl Syscalls<{ Syscall::AREA_UNMAP }> for Syscall {
+    type Domain0 = Handle<Area>;
+    type Domain1 = VAddr;
+    type Error = AreaUnmapError;
+    async fn syscall(_: &Environment, (area, addr, ..): domain!()) -> codomain!() {
+        use proc::vmm::AreaUnmapError as E;
+        use AreaUnmapError::*;
+        let map = area.mapped(addr).map_err(|e| match e {
+            E::UnmapAnArea => UnmapAnArea,
+            E::NotFound => NotFound,
+        })?;
+        map.sync().await.map_err(|_| IOError)?;
+        area.unmap(addr).map_err(|e| match e {
+            E::UnmapAnArea => UnmapAnArea,
+            E::NotFound => NotFound,
+        })?;
+        Flow::Ok(())
+    }
+}
@@ -1,8 +1,10 @@
 mod debug;
+mod file;
 mod handle;
 mod memmap;
 mod memory;
 mod process;
+mod random;
 mod thread;
 
 use crate::prelude::*;
@@ -49,9 +51,31 @@ impl Environment {
             Syscall::THREAD_CREATE => solve::<{ Syscall::THREAD_CREATE }>(self, args).await,
             Syscall::THREAD_KILL => solve::<{ Syscall::THREAD_KILL }>(self, args).await,
             Syscall::THREAD_YIELD => solve::<{ Syscall::THREAD_YIELD }>(self, args).await,
+            Syscall::THREAD_SET_PRIORITY => {
+                solve::<{ Syscall::THREAD_SET_PRIORITY }>(self, args).await
+            }
+            Syscall::THREAD_GET_PRIORITY => {
+                solve::<{ Syscall::THREAD_GET_PRIORITY }>(self, args).await
+            }
+            Syscall::THREAD_SET_AFFINITY => {
+                solve::<{ Syscall::THREAD_SET_AFFINITY }>(self, args).await
+            }
+            Syscall::THREAD_GET_AFFINITY => {
+                solve::<{ Syscall::THREAD_GET_AFFINITY }>(self, args).await
+            }
             Syscall::AREA_CREATE => solve::<{ Syscall::AREA_CREATE }>(self, args).await,
             Syscall::AREA_FIND_CREATE => solve::<{ Syscall::AREA_FIND_CREATE }>(self, args).await,
             Syscall::AREA_MAP => solve::<{ Syscall::AREA_MAP }>(self, args).await,
+            Syscall::AREA_MAP_FILE => solve::<{ Syscall::AREA_MAP_FILE }>(self, args).await,
+            Syscall::AREA_UNMAP => solve::<{ Syscall::AREA_UNMAP }>(self, args).await,
+            Syscall::PROCESS_RANDOMIZE => solve::<{ Syscall::PROCESS_RANDOMIZE }>(self, args).await,
+            Syscall::RANDOM_GET => solve::<{ Syscall::RANDOM_GET }>(self, args).await,
+            Syscall::FILE_OPEN => solve::<{ Syscall::FILE_OPEN }>(self, args).await,
+            Syscall::FILE_READ => solve::<{ Syscall::FILE_READ }>(self, args).await,
+            Syscall::FILE_WRITE => solve::<{ Syscall::FILE_WRITE }>(self, args).await,
+            Syscall::FILE_SEEK => solve::<{ Syscall::FILE_SEEK }>(self, args).await,
+            Syscall::FILE_STAT => solve::<{ Syscall::FILE_STAT }>(self, args).await,
+            Syscall::DIR_READ => solve::<{ Syscall::DIR_READ }>(self, args).await,
             _ => Flow::Err(UserError::General(GeneralError::InvaildSyscall)),
         }
     }
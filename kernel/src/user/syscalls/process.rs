@@ -2,3 +2,16 @@ use crate::prelude::*;
 use proc::process::Process;
 
 impl Object for Process {}
+
+impl_syscall!(PROCESS_RANDOMIZE, 0x6b0e4f93u32);
+
+#[async_trait::async_trait]
+impl Syscalls<{ Syscall::PROCESS_RANDOMIZE }> for Syscall {
+    type Domain0 = Handle<Process>;
+    type Domain1 = bool;
+    type Error = !;
+    async fn syscall(_: &Environment, (process, randomized, ..): domain!()) -> codomain!() {
+        process.space.set_randomized(randomized);
+        Flow::Ok(())
+    }
+}
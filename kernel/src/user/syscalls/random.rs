@@ -0,0 +1,33 @@
+use crate::prelude::*;
+
+impl_syscall!(RANDOM_GET, 0xa4d27c19u32);
+
+#[repr(u8)]
+pub enum RandomGetError {
+    BadBuffer,
+}
+
+impl SyscallError for RandomGetError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+#[async_trait::async_trait]
+impl Syscalls<{ Syscall::RANDOM_GET }> for Syscall {
+    type Domain0 = VAddr;
+    type Domain1 = usize;
+    type Codomain = usize;
+    type Error = RandomGetError;
+    async fn syscall(env: &Environment, (buffer_addr, buffer_len, ..): domain!()) -> codomain!() {
+        use RandomGetError::*;
+        let mut buffer = vec![0u8; usize::min(buffer_len, config::RANDOM_GET_MAX)];
+        rt::random::fill(&mut buffer);
+        env.process
+            .space
+            .write_buffer(buffer_addr, &buffer)
+            .await
+            .map_err(|_| BadBuffer)?;
+        Flow::Ok(buffer.len())
+    }
+}
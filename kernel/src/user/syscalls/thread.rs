@@ -29,11 +29,13 @@ impl Syscalls<{ Syscall::THREAD_CREATE }> for Syscall {
     async fn syscall(env: &Environment, (process, pc, opaque, ..): domain!()) -> codomain!() {
         use ProcessSpawnError as E;
         use SyscallThreadCreateError::*;
-        let thread = process.spawn(pc, opaque).map_err(|e| match e {
-            E::BadStatus => BadStatus,
-            E::OutOfMemory => OutOfMemory,
-            E::OutOfVirtualMemory => OutOfVirtualMemory,
-        })?;
+        let thread = process
+            .spawn(pc, opaque, |_| Vec::new())
+            .map_err(|e| match e {
+                E::BadStatus => BadStatus,
+                E::OutOfMemory => OutOfMemory,
+                E::OutOfVirtualMemory => OutOfVirtualMemory,
+            })?;
         let handle_id = env.process.handle_set.push(Handle::new(thread));
         Flow::Ok(handle_id)
     }
@@ -75,3 +77,124 @@ impl Syscalls<{ Syscall::THREAD_EXIT }> for Syscall {
         env.thread_exit(exit_code as isize).await?;
     }
 }
+
+impl_syscall!(THREAD_SET_PRIORITY, 0x2e6a91c4u32);
+
+#[repr(u8)]
+pub enum ThreadSetPriorityError {
+    BadStatus,
+    PermissionDenied,
+}
+
+impl SyscallError for ThreadSetPriorityError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+#[async_trait::async_trait]
+impl Syscalls<{ Syscall::THREAD_SET_PRIORITY }> for Syscall {
+    type Domain0 = Handle<Thread>;
+    type Domain1 = ScheduleClass;
+    type Domain2 = Priority;
+    type Error = ThreadSetPriorityError;
+    async fn syscall(env: &Environment, (thread, class, priority, ..): domain!()) -> codomain!() {
+        use ThreadSetPriorityError::*;
+        if class == ScheduleClass::Fifo && !Arc::ptr_eq(&env.process, sched::scheduler::initproc())
+        {
+            return Flow::Err(PermissionDenied.into());
+        }
+        let task = thread.task().ok_or(BadStatus)?;
+        task.set_class(class);
+        task.set_priority(priority);
+        Flow::Ok(())
+    }
+}
+
+impl_syscall!(THREAD_GET_PRIORITY, 0x9b03d75eu32);
+
+#[repr(u8)]
+pub enum ThreadGetPriorityError {
+    BadStatus,
+}
+
+impl SyscallError for ThreadGetPriorityError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+#[async_trait::async_trait]
+impl Syscalls<{ Syscall::THREAD_GET_PRIORITY }> for Syscall {
+    type Domain0 = Handle<Thread>;
+    type Codomain = usize;
+    type Error = ThreadGetPriorityError;
+    async fn syscall(_: &Environment, (thread, ..): domain!()) -> codomain!() {
+        use ThreadGetPriorityError::*;
+        let task = thread.task().ok_or(BadStatus)?;
+        let class = u8::from(task.class()) as usize;
+        Flow::Ok(class << 32 | task.priority().value() as usize)
+    }
+}
+
+fn harts() -> usize {
+    rt::thread::threads()
+        .keys()
+        .filter(|&&id| id < usize::BITS as usize)
+        .fold(0, |mask, &id| mask | 1 << id)
+}
+
+impl_syscall!(THREAD_SET_AFFINITY, 0x71c85a3du32);
+
+#[repr(u8)]
+pub enum ThreadSetAffinityError {
+    BadStatus,
+    InvaildMask,
+}
+
+impl SyscallError for ThreadSetAffinityError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+#[async_trait::async_trait]
+impl Syscalls<{ Syscall::THREAD_SET_AFFINITY }> for Syscall {
+    type Domain0 = Handle<Thread>;
+    type Domain1 = usize;
+    type Error = ThreadSetAffinityError;
+    async fn syscall(_: &Environment, (thread, mask, ..): domain!()) -> codomain!() {
+        use ThreadSetAffinityError::*;
+        if mask == 0 || mask & !harts() != 0 {
+            return Flow::Err(InvaildMask.into());
+        }
+        let task = thread.task().ok_or(BadStatus)?;
+        task.set_affinity(mask);
+        Flow::Ok(())
+    }
+}
+
+impl_syscall!(THREAD_GET_AFFINITY, 0xc4f0e218u32);
+
+#[repr(u8)]
+pub enum ThreadGetAffinityError {
+    BadStatus,
+}
+
+impl SyscallError for ThreadGetAffinityError {
+    fn into_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+#[async_trait::async_trait]
+impl Syscalls<{ Syscall::THREAD_GET_AFFINITY }> for Syscall {
+    type Domain0 = Handle<Thread>;
+    type Codomain = usize;
+    type Error = ThreadGetAffinityError;
+    async fn syscall(_: &Environment, (thread, ..): domain!()) -> codomain!() {
+        use ThreadGetAffinityError::*;
+        let task = thread.task().ok_or(BadStatus)?;
+        Flow::Ok(task.affinity() & harts())
+    }
+}